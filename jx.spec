@@ -1,17 +1,19 @@
 /* workflow */
-%token LBRAC RBRAC LSQBRAC RSQBRAC LPAREN RPAREN COLON COMMA INTCONST DOUBLECONST BOOLCONST STRCONST NULL FOR IN IF ADD MINUS MUL DIV MOD NOT AND OR EQ NE LT LE GT GE ID
+%token LBRAC RBRAC LSQBRAC RSQBRAC LPAREN RPAREN COLON COMMA DOT INTCONST DOUBLECONST BOOLCONST STRCONST NULL FOR IN IF ELSE ADD MINUS MUL DIV MOD NOT AND OR EQ NE LT LE GT GE ID
 %start JX
 %%
 
 JX : LBRAC key_val_list RBRAC;
 
 key_val_list : key_val | key_val COMMA key_val_list;
-key_val : STRCONST COLON expr;
-expr : value | jx_expr;
+/* key should evaluate to a string */
+key_val : binary_expr COLON expr;
+expr : cond_expr | list_compre_expr;
 value : STRCONST
     | INTCONST
     | DOUBLECONST
     | BOOLCONST
+    | NULL
     | object
     | list;
 
@@ -21,14 +23,31 @@ list : LSQBRAC RSQBRAC
     | LSQBRAC expr_list RSQBRAC;
 expr_list : expr | expr COMMA expr_list;
 
-jx_expr : ID
-    | jx_arith_expr
-    | list_compre_expr;
-
-jx_arith_expr : arith_const ADD jx_arith_expr;
-arith_const : STRCONST | INTCONST | DOUBLECONST;
-
-list_compre_expr : value FOR ID IN iterable_expr opt_list_compre_expr;
-iterable_expr : list;
-opt_list_compre_expr : FOR ID IN iterable_expr opt_list_compre_expr |;
+/* only the taken branch is evaluated */
+cond_expr : binary_expr | binary_expr IF binary_expr ELSE cond_expr;
+
+/* lowest to highest precedence, binary operators are left associative */
+binary_expr : and_expr | binary_expr OR and_expr;
+and_expr : not_expr | and_expr AND not_expr;
+not_expr : cmp_expr | NOT not_expr;
+cmp_expr : add_expr | cmp_expr cmp_op add_expr;
+cmp_op : EQ | NE | LT | LE | GT | GE;
+add_expr : mul_expr | add_expr ADD mul_expr | add_expr MINUS mul_expr;
+mul_expr : unary_expr | mul_expr MUL unary_expr | mul_expr DIV unary_expr | mul_expr MOD unary_expr;
+unary_expr : MINUS unary_expr | postfix_expr;
+/* index or slice, negative positions count from the end */
+postfix_expr : primary_expr
+    | postfix_expr LSQBRAC binary_expr RSQBRAC
+    | postfix_expr LSQBRAC opt_binary_expr COLON opt_binary_expr RSQBRAC
+    /* dot access dialect, a.b is a["b"] */
+    | postfix_expr DOT ID;
+opt_binary_expr : binary_expr |;
+primary_expr : value | ID | func_call | LPAREN cond_expr RPAREN;
+func_call : ID LPAREN RPAREN | ID LPAREN expr_list RPAREN;
+
+list_compre_expr : cond_expr compre_clause opt_list_compre_expr;
+compre_clause : FOR ID IN iterable_expr opt_compre_cond;
+iterable_expr : binary_expr;
+opt_compre_cond : IF binary_expr opt_compre_cond |;
+opt_list_compre_expr : compre_clause opt_list_compre_expr |;
 
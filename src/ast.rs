@@ -1,79 +1,135 @@
+use super::eval::EvalErr;
+use super::span::{Span, Spanned};
 use std::collections::HashMap;
 use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::{slice, vec};
 
+/// AST node with its location, for values the location of the expression they come from
+pub type Ast = Box<Spanned<AstNode>>;
+
+#[derive(Clone)]
 pub enum AstNode {
-    INTVAL(i32),
+    INTVAL(i64),
     DOUBLEVAL(f64),
     STRVAL(String),
     BOOLVAL(bool),
     NULLVAL,
     // key value pairs
-    OBJECT(HashMap<String, Box<AstNode>>),
-    LIST(Vec<Box<AstNode>>),
+    OBJECT(ObjectMap),
+    // object with keys that are expressions other than string, evaluated to OBJECT
+    OBJEXPR(Vec<(Ast, Ast)>),
+    LIST(Vec<Ast>),
     // Variable
     VAR(String),
     ADD {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     SUB {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     MUL {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     DIV {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     MOD {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     AND {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     OR {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     EQ {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     NE {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     GT {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     GE {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     LT {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
     },
     LE {
-        left: Box<AstNode>,
-        right: Box<AstNode>,
+        left: Ast,
+        right: Ast,
+    },
+    NOT {
+        expr: Ast,
+    },
+    // unary minus
+    NEG {
+        expr: Ast,
     },
     /// list comprehension
     COMPRE {
-        expr: Box<AstNode>,
-        var: String,
-        iter_expr: Box<AstNode>,
+        expr: Ast,
+        // outermost loop first
+        clauses: Vec<CompreClause>,
     },
     FUNC {
         name: String,
-        params: Vec<Box<AstNode>>,
+        params: Vec<Ast>,
+    },
+    // list[index] or object[key]
+    LOOKUP {
+        expr: Ast,
+        index: Ast,
     },
+    // list[start:end], a missing bound is None
+    SLICE {
+        expr: Ast,
+        start: Option<Ast>,
+        end: Option<Ast>,
+    },
+    // `then if cond else orelse`, only the taken branch is evaluated
+    IFELSE {
+        cond: Ast,
+        then: Ast,
+        orelse: Ast,
+    },
+    // result of a failed operation, located where it failed
+    ERROR(EvalErr),
+}
+
+/// `for var in iter_expr if cond ...` in list comprehension
+#[derive(Clone)]
+pub struct CompreClause {
+    pub var: String,
+    pub iter_expr: Ast,
+    // filters, item is kept only if all of them are true
+    pub conds: Vec<Ast>,
+}
+
+impl fmt::Display for CompreClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "for {} in {}", self.var, self.iter_expr)?;
+        for cond in &self.conds {
+            write!(f, " if {}", cond)?;
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for AstNode {
@@ -85,24 +141,35 @@ impl fmt::Display for AstNode {
             AstNode::BOOLVAL(val) => write!(f, "{}", val),
             AstNode::NULLVAL => write!(f, "null"),
             AstNode::OBJECT(keyval_list) => {
-                if let Err(err) = write!(f, "{{ ") {
-                    return Err(err);
+                write!(f, "{{ ")?;
+                for (i, (key, value)) in keyval_list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\" : {}", key, value)?;
                 }
-                for (key, value) in keyval_list {
-                    if let Err(err) = write!(f, "\"{}\" : {}", key, value) {
-                        return Err(err);
+                write!(f, " }}")
+            }
+            AstNode::OBJEXPR(pairs) => {
+                write!(f, "{{ ")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match &key.node {
+                        AstNode::STRVAL(key) => write!(f, "\"{}\" : {}", key, value)?,
+                        _ => write!(f, "{} : {}", key, value)?,
                     }
                 }
                 write!(f, " }}")
             }
             AstNode::LIST(list) => {
-                if let Err(err) = write!(f, "[ ") {
-                    return Err(err);
-                }
-                for node in list {
-                    if let Err(err) = write!(f, "{}", node) {
-                        return Err(err);
+                write!(f, "[ ")?;
+                for (i, node) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
                     }
+                    write!(f, "{}", node)?;
                 }
                 write!(f, " ]")
             }
@@ -120,48 +187,93 @@ impl fmt::Display for AstNode {
             | AstNode::GE { left, right }
             | AstNode::LT { left, right }
             | AstNode::LE { left, right } => {
-                write!(f, "{} {} {}", left, self.operator_str(), right)
+                fmt_operand(f, left)?;
+                write!(f, " {} ", self.operator_str())?;
+                fmt_operand(f, right)
             }
-            AstNode::COMPRE {
-                expr,
-                var,
-                iter_expr,
-            } => write!(f, "[ {} for {} in {} ]", expr, var, iter_expr),
-            AstNode::FUNC { name, params } => {
-                if let Err(err) = write!(f, "{}(", name) {
-                    return Err(err);
+            AstNode::NOT { expr } => {
+                write!(f, "not ")?;
+                fmt_operand(f, expr)
+            }
+            AstNode::NEG { expr } => {
+                write!(f, "-")?;
+                fmt_operand(f, expr)
+            }
+            AstNode::COMPRE { expr, clauses } => {
+                write!(f, "{}", expr)?;
+                for clause in clauses {
+                    write!(f, " {}", clause)?;
                 }
+                Ok(())
+            }
+            AstNode::FUNC { name, params } => {
+                write!(f, "{}(", name)?;
                 // all params except last one
-                for param in params.iter().take(params.len() - 1) {
-                    if let Err(err) = write!(f, "{}, ", param) {
-                        return Err(err);
-                    }
+                for param in params.iter().take(params.len().saturating_sub(1)) {
+                    write!(f, "{}, ", param)?;
                 }
                 // last param
-                match params.iter().last() {
-                    Some(param) => {
-                        if let Err(err) = write!(f, "{}", param) {
-                            return Err(err);
-                        }
-                    }
-                    None => (),
-                };
+                if let Some(param) = params.iter().last() {
+                    write!(f, "{}", param)?;
+                }
                 write!(f, ")")
             }
+            AstNode::LOOKUP { expr, index } => {
+                fmt_operand(f, expr)?;
+                write!(f, "[{}]", index)
+            }
+            AstNode::SLICE { expr, start, end } => {
+                fmt_operand(f, expr)?;
+                write!(f, "[")?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "]")
+            }
+            AstNode::IFELSE { cond, then, orelse } => {
+                // conditional nests to the right without parentheses
+                fmt_operand(f, then)?;
+                write!(f, " if ")?;
+                fmt_operand(f, cond)?;
+                write!(f, " else {}", orelse)
+            }
+            AstNode::ERROR(err) => write!(f, "error(\"{}\")", err),
         }
     }
 }
 
+// parenthesize operand that is itself an operation, so precedence is preserved
+fn fmt_operand(f: &mut fmt::Formatter, operand: &AstNode) -> fmt::Result {
+    if operand.is_operation() {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
 impl AstNode {
-    fn operator_str(&self) -> &str {
+    pub fn at(self, span: Span) -> Ast {
+        Box::new(Spanned::new(self, span))
+    }
+
+    /// node that is not from any input
+    pub fn boxed(self) -> Ast {
+        self.at(Span::default())
+    }
+
+    pub fn operator_str(&self) -> &str {
         match &self {
             AstNode::ADD { left: _, right: _ } => "+",
             AstNode::SUB { left: _, right: _ } => "-",
             AstNode::MUL { left: _, right: _ } => "*",
             AstNode::DIV { left: _, right: _ } => "/",
             AstNode::MOD { left: _, right: _ } => "%",
-            AstNode::AND { left: _, right: _ } => "&&",
-            AstNode::OR { left: _, right: _ } => "||",
+            AstNode::AND { left: _, right: _ } => "and",
+            AstNode::OR { left: _, right: _ } => "or",
             AstNode::EQ { left: _, right: _ } => "==",
             AstNode::NE { left: _, right: _ } => "!=",
             AstNode::GT { left: _, right: _ } => ">",
@@ -172,63 +284,213 @@ impl AstNode {
         }
     }
 
-    pub fn is_int(&self) -> bool {
+    pub fn is_operation(&self) -> bool {
+        matches!(
+            self,
+            AstNode::ADD { .. }
+                | AstNode::SUB { .. }
+                | AstNode::MUL { .. }
+                | AstNode::DIV { .. }
+                | AstNode::MOD { .. }
+                | AstNode::AND { .. }
+                | AstNode::OR { .. }
+                | AstNode::EQ { .. }
+                | AstNode::NE { .. }
+                | AstNode::GT { .. }
+                | AstNode::GE { .. }
+                | AstNode::LT { .. }
+                | AstNode::LE { .. }
+                | AstNode::NOT { .. }
+                | AstNode::NEG { .. }
+                | AstNode::IFELSE { .. }
+        )
+    }
+
+    /// name of the value type, used in error messages
+    pub fn type_name(&self) -> &str {
         match &self {
-            AstNode::INTVAL(_) => true,
-            _ => false,
+            AstNode::INTVAL(_) => "int",
+            AstNode::DOUBLEVAL(_) => "double",
+            AstNode::STRVAL(_) => "string",
+            AstNode::BOOLVAL(_) => "boolean",
+            AstNode::NULLVAL => "null",
+            AstNode::OBJECT(_) => "object",
+            AstNode::OBJEXPR(_) => "object with computed keys",
+            AstNode::LIST(_) => "list",
+            AstNode::ERROR(_) => "error",
+            _ => "expression",
         }
     }
 
+    pub fn is_int(&self) -> bool {
+        matches!(self, AstNode::INTVAL(_))
+    }
+
     pub fn is_double(&self) -> bool {
-        match &self {
-            AstNode::DOUBLEVAL(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::DOUBLEVAL(_))
     }
 
     pub fn is_str(&self) -> bool {
-        match &self {
-            AstNode::STRVAL(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::STRVAL(_))
     }
 
     pub fn is_bool(&self) -> bool {
-        match &self {
-            AstNode::BOOLVAL(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::BOOLVAL(_))
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, AstNode::NULLVAL)
     }
 
     pub fn is_obj(&self) -> bool {
-        match &self {
-            AstNode::OBJECT(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::OBJECT(_))
     }
 
     pub fn is_list(&self) -> bool {
-        match &self {
-            AstNode::LIST(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::LIST(_))
     }
 
     pub fn is_var(&self) -> bool {
-        match &self {
-            AstNode::VAR(_) => true,
-            _ => false,
-        }
+        matches!(self, AstNode::VAR(_))
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, AstNode::ERROR(_))
     }
 
     pub fn is_list_compre(&self) -> bool {
-        match &self {
-            AstNode::COMPRE {
-                expr: _,
-                var: _,
-                iter_expr: _,
-            } => true,
-            _ => false,
+        matches!(self, AstNode::COMPRE { .. })
+    }
+
+    /// sort the keys of this and all nested objects
+    pub fn sort_keys(&mut self) {
+        match self {
+            AstNode::OBJECT(keyval_pairs) => {
+                keyval_pairs.sort_keys();
+                for value in keyval_pairs.values_mut() {
+                    value.sort_keys();
+                }
+            }
+            AstNode::LIST(list) => {
+                for elem in list {
+                    elem.sort_keys();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// key value pairs of an object, in the order the keys are inserted
+#[derive(Clone, Default)]
+pub struct ObjectMap {
+    pairs: Vec<(String, Ast)>,
+    // position of each key in pairs
+    index: HashMap<String, usize>,
+}
+
+impl ObjectMap {
+    pub fn new() -> ObjectMap {
+        ObjectMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Ast> {
+        self.index.get(key).map(|i| &self.pairs[*i].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// a new key goes to the end, an existing key keeps its position and the old value is returned
+    pub fn insert(&mut self, key: String, value: Ast) -> Option<Ast> {
+        if let Some(i) = self.index.get(&key) {
+            return Some(std::mem::replace(&mut self.pairs[*i].1, value));
+        }
+        self.index.insert(key.clone(), self.pairs.len());
+        self.pairs.push((key, value));
+        None
+    }
+
+    /// remove the key, the keys after it keep their order
+    pub fn remove(&mut self, key: &str) -> Option<Ast> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.pairs.remove(i);
+        for (key, _) in &self.pairs[i..] {
+            *self.index.get_mut(key).unwrap() -= 1;
         }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (String, Ast)> {
+        self.pairs.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.pairs.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Ast> {
+        self.pairs.iter().map(|(_, value)| value)
+    }
+
+    /// keys can not be changed in place, they are indexed
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Ast> {
+        self.pairs.iter_mut().map(|(_, value)| value)
+    }
+
+    /// canonical order, keys sorted by their bytes
+    pub fn sort_keys(&mut self) {
+        self.pairs.sort_by(|(l, _), (r, _)| l.cmp(r));
+        for (i, (key, _)) in self.pairs.iter().enumerate() {
+            *self.index.get_mut(key).unwrap() = i;
+        }
+    }
+}
+
+impl Index<&str> for ObjectMap {
+    type Output = Ast;
+
+    fn index(&self, key: &str) -> &Ast {
+        match self.get(key) {
+            Some(value) => value,
+            None => panic!("no key \"{}\" in object", key),
+        }
+    }
+}
+
+impl FromIterator<(String, Ast)> for ObjectMap {
+    fn from_iter<I: IntoIterator<Item = (String, Ast)>>(iter: I) -> ObjectMap {
+        let mut map = ObjectMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl IntoIterator for ObjectMap {
+    type Item = (String, Ast);
+    type IntoIter = vec::IntoIter<(String, Ast)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ObjectMap {
+    type Item = &'a (String, Ast);
+    type IntoIter = slice::Iter<'a, (String, Ast)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.iter()
     }
 }
@@ -0,0 +1,438 @@
+use super::ast::{Ast, AstNode};
+use super::eval::EvalErr;
+use super::regex::Regex;
+use super::span::Span;
+use std::convert::TryFrom;
+
+/// call a built-in function with evaluated arguments, span is the location of the call
+pub fn call(name: &str, args: Vec<Ast>, span: &Span) -> Result<Ast, EvalErr> {
+    let result = match name {
+        "range" => range(args, span),
+        "format" => format(args),
+        "join" => join(args),
+        "len" => len(args),
+        "ceil" => round(args, f64::ceil),
+        "floor" => round(args, f64::floor),
+        "basename" => basename(args),
+        "dirname" => dirname(args),
+        "escape" => escape(args),
+        "keys" => keys(args, span),
+        "values" => values(args),
+        "items" => items(args, span),
+        "like" => like(args),
+        _ => Err(String::from("undefined function")),
+    };
+    match result {
+        Ok(val) => Ok(val.at(span.clone())),
+        Err(reason) => Err(EvalErr::Func {
+            name: String::from(name),
+            reason,
+        }),
+    }
+}
+
+/// check the number of arguments is within [min, max]
+pub fn check_arg_count(args: &[Ast], min: usize, max: usize) -> Result<(), String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(format!("expect {} arguments, got {}", expected, args.len()));
+    }
+    Ok(())
+}
+
+fn arg_type_err(index: usize, expected: &str, arg: &AstNode) -> String {
+    format!(
+        "argument {} should be {}, got {}",
+        index + 1,
+        expected,
+        arg.type_name()
+    )
+}
+
+fn int_arg(args: &[Ast], index: usize) -> Result<i64, String> {
+    match &args[index].node {
+        AstNode::INTVAL(val) => Ok(*val),
+        other => Err(arg_type_err(index, "int", other)),
+    }
+}
+
+fn str_arg(args: &[Ast], index: usize) -> Result<&str, String> {
+    match &args[index].node {
+        AstNode::STRVAL(val) => Ok(val),
+        other => Err(arg_type_err(index, "string", other)),
+    }
+}
+
+fn list_arg(args: &[Ast], index: usize) -> Result<&Vec<Ast>, String> {
+    match &args[index].node {
+        AstNode::LIST(list) => Ok(list),
+        other => Err(arg_type_err(index, "list", other)),
+    }
+}
+
+fn obj_arg(args: Vec<Ast>) -> Result<Vec<(String, Ast)>, String> {
+    check_arg_count(&args, 1, 1)?;
+    match args.into_iter().next().unwrap().node {
+        AstNode::OBJECT(keyval_pairs) => Ok(keyval_pairs.into_iter().collect()),
+        other => Err(arg_type_err(0, "object", &other)),
+    }
+}
+
+// range(stop), range(start, stop) or range(start, stop, step)
+fn range(args: Vec<Ast>, span: &Span) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 3)?;
+    let (start, stop) = match args.len() {
+        1 => (0, int_arg(&args, 0)?),
+        _ => (int_arg(&args, 0)?, int_arg(&args, 1)?),
+    };
+    let step = match args.len() {
+        3 => int_arg(&args, 2)?,
+        _ => 1,
+    };
+    if step == 0 {
+        return Err(String::from("step must not be zero"));
+    }
+    let mut list = vec![];
+    let mut i = start;
+    while (step > 0 && i < stop) || (step < 0 && i > stop) {
+        list.push(AstNode::INTVAL(i).at(span.clone()));
+        // stop before overflow, the next value would be past stop anyway
+        i = match i.checked_add(step) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(AstNode::LIST(list))
+}
+
+// printf style, supports %d %i %e %E %f %F %g %G %s %%
+fn format(args: Vec<Ast>) -> Result<AstNode, String> {
+    if args.is_empty() {
+        return Err(String::from("expect at least 1 argument, got 0"));
+    }
+    let fmt: Vec<char> = str_arg(&args, 0)?.chars().collect();
+    let mut output = String::new();
+    let mut next_arg = 1;
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != '%' {
+            output.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        let spec = parse_spec(&fmt, &mut i)?;
+        if spec.conv == '%' {
+            output.push('%');
+            continue;
+        }
+        if next_arg >= args.len() {
+            return Err(String::from("too few arguments for format string"));
+        }
+        output.push_str(&spec.apply(&args[next_arg], next_arg)?);
+        next_arg += 1;
+    }
+    if next_arg < args.len() {
+        return Err(String::from("too many arguments for format string"));
+    }
+    Ok(AstNode::STRVAL(output))
+}
+
+struct FormatSpec {
+    left_align: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    zero_pad: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    conv: char,
+}
+
+// parse %[flags][width][.precision]conv, i points at % and is moved past conv
+fn parse_spec(fmt: &[char], i: &mut usize) -> Result<FormatSpec, String> {
+    let mut spec = FormatSpec {
+        left_align: false,
+        plus_sign: false,
+        space_sign: false,
+        zero_pad: false,
+        alternate: false,
+        width: 0,
+        precision: None,
+        conv: '%',
+    };
+    *i += 1;
+    while *i < fmt.len() {
+        match fmt[*i] {
+            '-' => spec.left_align = true,
+            '+' => spec.plus_sign = true,
+            ' ' => spec.space_sign = true,
+            '0' => spec.zero_pad = true,
+            '#' => spec.alternate = true,
+            _ => break,
+        }
+        *i += 1;
+    }
+    while *i < fmt.len() && fmt[*i].is_ascii_digit() {
+        spec.width = spec.width * 10 + fmt[*i].to_digit(10).unwrap() as usize;
+        *i += 1;
+    }
+    if *i < fmt.len() && fmt[*i] == '.' {
+        *i += 1;
+        let mut precision = 0;
+        while *i < fmt.len() && fmt[*i].is_ascii_digit() {
+            precision = precision * 10 + fmt[*i].to_digit(10).unwrap() as usize;
+            *i += 1;
+        }
+        spec.precision = Some(precision);
+    }
+    if *i >= fmt.len() {
+        return Err(String::from("incomplete format specifier"));
+    }
+    spec.conv = fmt[*i];
+    *i += 1;
+    match spec.conv {
+        'd' | 'i' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 's' | '%' => Ok(spec),
+        c => Err(format!("unsupported format specifier %{}", c)),
+    }
+}
+
+impl FormatSpec {
+    fn apply(&self, arg: &AstNode, index: usize) -> Result<String, String> {
+        let (sign, body) = match (self.conv, arg) {
+            ('s', AstNode::STRVAL(val)) => {
+                let val = match self.precision {
+                    Some(precision) => val.chars().take(precision).collect(),
+                    None => val.clone(),
+                };
+                return Ok(self.pad("", &val, false));
+            }
+            ('s', other) => return Err(arg_type_err(index, "string", other)),
+            ('d' | 'i', AstNode::INTVAL(val)) => {
+                (self.sign(*val < 0), val.unsigned_abs().to_string())
+            }
+            ('d' | 'i', other) => return Err(arg_type_err(index, "int", other)),
+            (_, AstNode::INTVAL(val)) => self.format_double(*val as f64),
+            (_, AstNode::DOUBLEVAL(val)) => self.format_double(*val),
+            (_, other) => return Err(arg_type_err(index, "number", other)),
+        };
+        Ok(self.pad(sign, &body, true))
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    fn format_double(&self, val: f64) -> (&'static str, String) {
+        let sign = self.sign(val.is_sign_negative() && val != 0.0);
+        let val = val.abs();
+        if !val.is_finite() {
+            let body = if val.is_nan() { "nan" } else { "inf" };
+            return (sign, self.case(body.to_string()));
+        }
+        let precision = self.precision.unwrap_or(6);
+        let body = match self.conv {
+            'f' | 'F' => format!("{:.*}", precision, val),
+            'e' | 'E' => format_exp(val, precision),
+            _ => {
+                // %g uses %e if the exponent is less than -4 or not less than precision
+                let precision = precision.max(1);
+                let exp = if val == 0.0 {
+                    0
+                } else {
+                    format!("{:.*e}", precision - 1, val)
+                        .split_once('e')
+                        .map(|(_, exp)| exp.parse::<i32>().unwrap())
+                        .unwrap()
+                };
+                let body = if exp < -4 || exp >= precision as i32 {
+                    format_exp(val, precision - 1)
+                } else {
+                    format!("{:.*}", (precision as i32 - 1 - exp) as usize, val)
+                };
+                if self.alternate {
+                    body
+                } else {
+                    strip_trailing_zeros(body)
+                }
+            }
+        };
+        (sign, self.case(body))
+    }
+
+    fn case(&self, body: String) -> String {
+        if self.conv.is_ascii_uppercase() {
+            body.to_uppercase()
+        } else {
+            body
+        }
+    }
+
+    fn pad(&self, sign: &str, body: &str, numeric: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        if len >= self.width {
+            return format!("{}{}", sign, body);
+        }
+        let fill = self.width - len;
+        if self.left_align {
+            format!("{}{}{}", sign, body, " ".repeat(fill))
+        } else if self.zero_pad && numeric {
+            format!("{}{}{}", sign, "0".repeat(fill), body)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), sign, body)
+        }
+    }
+}
+
+// C style exponent, e.g. 1.500000e+03
+fn format_exp(val: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, val);
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let exp = exp.parse::<i32>().unwrap();
+    let exp_sign = if exp < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, exp_sign, exp.abs())
+}
+
+// 1.500000 -> 1.5, 2.000e+03 -> 2e+03
+fn strip_trailing_zeros(body: String) -> String {
+    let (mantissa, exp) = match body.find('e') {
+        Some(pos) => (&body[..pos], &body[pos..]),
+        None => (&body[..], ""),
+    };
+    if !mantissa.contains('.') {
+        return body;
+    }
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+    format!("{}{}", mantissa, exp)
+}
+
+// join(list[, delimiter]), delimiter defaults to a space
+fn join(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 2)?;
+    let delim = match args.len() {
+        2 => str_arg(&args, 1)?,
+        _ => " ",
+    };
+    let mut strs = vec![];
+    for elem in list_arg(&args, 0)? {
+        match &elem.node {
+            AstNode::STRVAL(val) => strs.push(val.as_str()),
+            other => return Err(format!("can only join strings, got {}", other.type_name())),
+        }
+    }
+    Ok(AstNode::STRVAL(strs.join(delim)))
+}
+
+fn len(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 1)?;
+    let len = list_arg(&args, 0)?.len();
+    match i64::try_from(len) {
+        Ok(len) => Ok(AstNode::INTVAL(len)),
+        Err(_) => Err(format!("length {} is out of range", len)),
+    }
+}
+
+// ceil and floor, int stays int
+fn round(args: Vec<Ast>, op: fn(f64) -> f64) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 1)?;
+    match &args[0].node {
+        AstNode::INTVAL(val) => Ok(AstNode::INTVAL(*val)),
+        AstNode::DOUBLEVAL(val) => Ok(AstNode::DOUBLEVAL(op(*val))),
+        other => Err(arg_type_err(0, "number", other)),
+    }
+}
+
+// basename(path[, suffix]), same as basename(1)
+fn basename(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 2)?;
+    let path = str_arg(&args, 0)?;
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        let base = if path.is_empty() { "." } else { "/" };
+        return Ok(AstNode::STRVAL(String::from(base)));
+    }
+    let mut base = match trimmed.rfind('/') {
+        Some(pos) => &trimmed[pos + 1..],
+        None => trimmed,
+    };
+    if args.len() == 2 {
+        let suffix = str_arg(&args, 1)?;
+        if base != suffix {
+            base = base.strip_suffix(suffix).unwrap_or(base);
+        }
+    }
+    Ok(AstNode::STRVAL(String::from(base)))
+}
+
+// same as dirname(1)
+fn dirname(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 1)?;
+    let path = str_arg(&args, 0)?;
+    let trimmed = path.trim_end_matches('/');
+    let dir = match trimmed.rfind('/') {
+        Some(pos) => match trimmed[..pos].trim_end_matches('/') {
+            "" => "/",
+            dir => dir,
+        },
+        None if path.starts_with('/') => "/",
+        None => ".",
+    };
+    Ok(AstNode::STRVAL(String::from(dir)))
+}
+
+// quote the string for shell
+fn escape(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 1, 1)?;
+    let val = str_arg(&args, 0)?;
+    Ok(AstNode::STRVAL(format!("'{}'", val.replace('\'', "'\\''"))))
+}
+
+fn keys(args: Vec<Ast>, span: &Span) -> Result<AstNode, String> {
+    let keyval_pairs = obj_arg(args)?;
+    Ok(AstNode::LIST(
+        keyval_pairs
+            .into_iter()
+            .map(|(key, _)| AstNode::STRVAL(key).at(span.clone()))
+            .collect(),
+    ))
+}
+
+fn values(args: Vec<Ast>) -> Result<AstNode, String> {
+    let keyval_pairs = obj_arg(args)?;
+    Ok(AstNode::LIST(
+        keyval_pairs.into_iter().map(|(_, value)| value).collect(),
+    ))
+}
+
+// list of [key, value] pairs
+fn items(args: Vec<Ast>, span: &Span) -> Result<AstNode, String> {
+    let keyval_pairs = obj_arg(args)?;
+    Ok(AstNode::LIST(
+        keyval_pairs
+            .into_iter()
+            .map(|(key, value)| {
+                let key = AstNode::STRVAL(key).at(span.clone());
+                AstNode::LIST(vec![key, value]).at(span.clone())
+            })
+            .collect(),
+    ))
+}
+
+// like(string, regex), true if the extended regular expression matches part of the string
+fn like(args: Vec<Ast>) -> Result<AstNode, String> {
+    check_arg_count(&args, 2, 2)?;
+    let val = str_arg(&args, 0)?;
+    let regex = Regex::new(str_arg(&args, 1)?)?;
+    Ok(AstNode::BOOLVAL(regex.is_match(val)))
+}
@@ -0,0 +1,806 @@
+use super::ast::{Ast, AstNode, CompreClause, ObjectMap};
+use super::builtins;
+use super::parser::{DuplicateKeys, ParserResult};
+use super::span::{Span, Spanned};
+use super::symbol_tab::{Symbol, SymbolTable};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum EvalErr {
+    // variable is not defined
+    Undefined(String),
+    // operand or argument of the wrong type
+    Type(String),
+    // arithmetic error, e.g. division by zero, integer overflow
+    Arith(String),
+    // symbols whose definitions depend on each other, in the order they are referenced
+    Cycle(Vec<String>),
+    // function is undefined or misused, e.g. wrong number or type of arguments
+    Func { name: String, reason: String },
+    // list index out of range or key not in object
+    Lookup(String),
+    // computed key that names an earlier key of its object, first is the location of that key
+    DuplicateKey { key: String, first: Span },
+}
+
+impl Error for EvalErr {}
+
+impl fmt::Display for EvalErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            EvalErr::Undefined(name) => write!(f, "undefined symbol {}", name),
+            EvalErr::Type(reason) => write!(f, "{}", reason),
+            EvalErr::Arith(reason) => write!(f, "{}", reason),
+            EvalErr::Cycle(names) => write!(f, "cyclic definition {}", names.join(" -> ")),
+            EvalErr::Func { name, reason } => write!(f, "{}(): {}", name, reason),
+            EvalErr::Lookup(reason) => write!(f, "{}", reason),
+            EvalErr::DuplicateKey { key, first } => {
+                write!(f, "duplicate key \"{}\", first defined at {}", key, first)
+            }
+        }
+    }
+}
+
+/// why evaluating an expression stopped
+enum Failure {
+    // the expression itself failed, the error value is located at the expression
+    Raised(EvalErr),
+    // error value of an operand, passed on unchanged
+    Propagated(Ast),
+}
+
+impl From<EvalErr> for Failure {
+    fn from(err: EvalErr) -> Failure {
+        Failure::Raised(err)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EvalOptions {
+    // applies to computed keys, the parser has already applied it to string keys
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for EvalOptions {
+    fn default() -> EvalOptions {
+        EvalOptions {
+            duplicate_keys: DuplicateKeys::ERROR,
+        }
+    }
+}
+
+/// evaluated document, see eval_all
+pub struct EvalResult {
+    // value tree, or the error value that the evaluation failed with
+    pub root: Ast,
+    // every error raised during evaluation as an ERROR value, in the order they were raised
+    pub errors: Vec<Ast>,
+    // problems that do not stop evaluation as ERROR values, e.g. duplicate computed keys
+    // under a lenient policy
+    pub warnings: Vec<Ast>,
+}
+
+/// evaluate the parsed document into a value tree, no variable, operator or comprehension remains.
+/// ctx holds variables supplied by the caller, they take precedence over those in result.tab
+pub fn eval(result: &ParserResult, ctx: &SymbolTable) -> Result<Ast, EvalErr> {
+    let root = eval_all(result, ctx, EvalOptions::default()).root;
+    match root.node {
+        AstNode::ERROR(err) => Err(err),
+        _ => Ok(root),
+    }
+}
+
+/// evaluate the document as eval does, but a failed operation produces an error value
+/// instead of aborting. the error value replaces the result of every operator, function call,
+/// list and object that uses it, the rest of the document is still evaluated so that all
+/// errors are found
+pub fn eval_all(result: &ParserResult, ctx: &SymbolTable, options: EvalOptions) -> EvalResult {
+    let mut evaluator = Evaluator {
+        ctx,
+        options,
+        tab: &result.tab,
+        locals: vec![],
+        values: HashMap::new(),
+        evaluating: vec![],
+        errors: vec![],
+        warnings: vec![],
+    };
+    let root = evaluator.eval_value(&result.root);
+    EvalResult {
+        root,
+        errors: evaluator.errors,
+        warnings: evaluator.warnings,
+    }
+}
+
+struct Evaluator<'a> {
+    ctx: &'a SymbolTable,
+    options: EvalOptions,
+    tab: &'a SymbolTable,
+    // variables bound by list comprehension, innermost last
+    locals: Vec<(String, Ast)>,
+    // symbols that are already evaluated
+    values: HashMap<String, Ast>,
+    // symbols being evaluated, used to detect cyclic definitions
+    evaluating: Vec<String>,
+    // errors raised so far
+    errors: Vec<Ast>,
+    warnings: Vec<Ast>,
+}
+
+impl Evaluator<'_> {
+    /// value of an operand, an error value stops the operation using it
+    fn eval_node(&mut self, node: &Spanned<AstNode>) -> Result<Ast, Failure> {
+        let val = self.eval_value(node);
+        match val.node {
+            AstNode::ERROR(_) => Err(Failure::Propagated(val)),
+            _ => Ok(val),
+        }
+    }
+
+    /// value of the node, or the error value it failed with
+    fn eval_value(&mut self, node: &Spanned<AstNode>) -> Ast {
+        match self.eval_expr(node) {
+            Ok(val) => val,
+            Err(Failure::Raised(err)) => self.raise(err, node.span.clone()),
+            Err(Failure::Propagated(val)) => val,
+        }
+    }
+
+    /// error value located at span, recorded in errors
+    fn raise(&mut self, err: EvalErr, span: Span) -> Ast {
+        let val = AstNode::ERROR(err).at(span);
+        // a definition is evaluated in define and where it is referenced, and a
+        // comprehension item once per iteration, report each location once
+        if !self.errors.iter().any(|raised| {
+            raised.span.start == val.span.start && raised.span.file() == val.span.file()
+        }) {
+            self.errors.push(val.clone());
+        }
+        val
+    }
+
+    /// the value has the span of the expression it comes from
+    fn eval_expr(&mut self, node: &Spanned<AstNode>) -> Result<Ast, Failure> {
+        let span = node.span.clone();
+        match &node.node {
+            AstNode::INTVAL(_)
+            | AstNode::DOUBLEVAL(_)
+            | AstNode::STRVAL(_)
+            | AstNode::BOOLVAL(_)
+            | AstNode::NULLVAL
+            | AstNode::ERROR(_) => Ok(Box::new(node.clone())),
+            AstNode::OBJECT(keyval_pairs) => {
+                let mut evaluated = ObjectMap::new();
+                for (key, value) in keyval_pairs {
+                    evaluated.insert(key.clone(), self.eval_value(value));
+                }
+                first_error(evaluated.values())?;
+                Ok(AstNode::OBJECT(evaluated).at(span))
+            }
+            AstNode::OBJEXPR(pairs) => {
+                let values: Vec<(Ast, Ast)> = pairs
+                    .iter()
+                    .map(|(key, value)| (self.eval_value(key), self.eval_value(value)))
+                    .collect();
+                first_error(values.iter().flat_map(|(name, value)| [name, value]))?;
+                let mut evaluated = ObjectMap::new();
+                // location of the key that first defined each name
+                let mut first_keys: HashMap<String, Span> = HashMap::new();
+                for ((key, _), (name, value)) in pairs.iter().zip(values) {
+                    let name = match name.node {
+                        AstNode::STRVAL(name) => name,
+                        other => {
+                            return Err(EvalErr::Type(format!(
+                                "object key {} is {}, expect string",
+                                key,
+                                other.type_name()
+                            ))
+                            .into())
+                        }
+                    };
+                    if let Some(first) = first_keys.get(&name) {
+                        let err = EvalErr::DuplicateKey {
+                            key: name.clone(),
+                            first: first.clone(),
+                        };
+                        match self.options.duplicate_keys {
+                            DuplicateKeys::ERROR => {
+                                return Err(Failure::Propagated(self.raise(err, key.span.clone())))
+                            }
+                            DuplicateKeys::KEEPFIRST => {
+                                self.warnings.push(AstNode::ERROR(err).at(key.span.clone()))
+                            }
+                            DuplicateKeys::KEEPLAST => {
+                                self.warnings.push(AstNode::ERROR(err).at(key.span.clone()));
+                                evaluated.insert(name, value);
+                            }
+                        }
+                        continue;
+                    }
+                    first_keys.insert(name.clone(), key.span.clone());
+                    evaluated.insert(name, value);
+                }
+                Ok(AstNode::OBJECT(evaluated).at(span))
+            }
+            AstNode::LIST(list) => {
+                let mut evaluated = vec![];
+                for elem in list {
+                    match &elem.node {
+                        // comprehension expands in place
+                        AstNode::COMPRE { .. } => match self.eval_compre(elem) {
+                            Ok(mut items) => evaluated.append(&mut items),
+                            // the comprehension failed, not the list around it
+                            Err(Failure::Raised(err)) => {
+                                evaluated.push(self.raise(err, elem.span.clone()))
+                            }
+                            Err(Failure::Propagated(val)) => evaluated.push(val),
+                        },
+                        _ => evaluated.push(self.eval_value(elem)),
+                    }
+                }
+                first_error(&evaluated)?;
+                Ok(AstNode::LIST(evaluated).at(span))
+            }
+            AstNode::VAR(name) => self.eval_var(name),
+            AstNode::ADD { left, right }
+            | AstNode::SUB { left, right }
+            | AstNode::MUL { left, right }
+            | AstNode::DIV { left, right }
+            | AstNode::MOD { left, right }
+            | AstNode::EQ { left, right }
+            | AstNode::NE { left, right }
+            | AstNode::GT { left, right }
+            | AstNode::GE { left, right }
+            | AstNode::LT { left, right }
+            | AstNode::LE { left, right } => {
+                let left = self.eval_node(left)?;
+                let right = self.eval_node(right)?;
+                Ok(eval_binary(node, &left, &right)?.at(span))
+            }
+            AstNode::AND { left, right } | AstNode::OR { left, right } => {
+                let left = self.eval_node(left)?;
+                // right operand is not evaluated once left decides the result
+                match (&node.node, &left.node) {
+                    (AstNode::AND { .. }, AstNode::BOOLVAL(false)) => {
+                        return Ok(AstNode::BOOLVAL(false).at(span))
+                    }
+                    (AstNode::OR { .. }, AstNode::BOOLVAL(true)) => {
+                        return Ok(AstNode::BOOLVAL(true).at(span))
+                    }
+                    _ => (),
+                }
+                let right = self.eval_node(right)?;
+                Ok(eval_binary(node, &left, &right)?.at(span))
+            }
+            AstNode::IFELSE { cond, then, orelse } => match self.eval_node(cond)?.node {
+                AstNode::BOOLVAL(true) => self.eval_node(then),
+                AstNode::BOOLVAL(false) => self.eval_node(orelse),
+                other => Err(EvalErr::Type(format!(
+                    "condition {} is {}, expect boolean",
+                    cond,
+                    other.type_name()
+                ))
+                .into()),
+            },
+            AstNode::NOT { expr } => match self.eval_node(expr)?.node {
+                AstNode::BOOLVAL(val) => Ok(AstNode::BOOLVAL(!val).at(span)),
+                other => Err(EvalErr::Type(format!(
+                    "unsupported operand type for not: {}",
+                    other.type_name()
+                ))
+                .into()),
+            },
+            AstNode::NEG { expr } => match self.eval_node(expr)?.node {
+                AstNode::INTVAL(val) => match val.checked_neg() {
+                    Some(val) => Ok(AstNode::INTVAL(val).at(span)),
+                    None => Err(EvalErr::Arith(format!("integer overflow in -{}", val)).into()),
+                },
+                AstNode::DOUBLEVAL(val) => Ok(AstNode::DOUBLEVAL(-val).at(span)),
+                AstNode::NULLVAL => Ok(AstNode::NULLVAL.at(span)),
+                other => Err(EvalErr::Type(format!(
+                    "unsupported operand type for -: {}",
+                    other.type_name()
+                ))
+                .into()),
+            },
+            AstNode::COMPRE { .. } => Ok(AstNode::LIST(self.eval_compre(node)?).at(span)),
+            AstNode::FUNC { name, params } => self.eval_func(name, params, &span),
+            AstNode::LOOKUP { expr, index } => {
+                let container = self.eval_node(expr)?;
+                let index = self.eval_node(index)?;
+                Ok(eval_lookup(&container, &index)?.at(span))
+            }
+            AstNode::SLICE { expr, start, end } => {
+                let list = match self.eval_node(expr)?.node {
+                    AstNode::LIST(list) => list,
+                    other => {
+                        return Err(EvalErr::Type(format!(
+                            "cannot slice {}, expect list",
+                            other.type_name()
+                        ))
+                        .into())
+                    }
+                };
+                let start = self.eval_slice_bound(start, 0, list.len())?;
+                let end = self.eval_slice_bound(end, list.len(), list.len())?;
+                let slice = list[start..end.max(start)].to_vec();
+                Ok(AstNode::LIST(slice).at(span))
+            }
+        }
+    }
+
+    /// negative bound counts from the end, out of range bound is clamped as in Python.
+    /// missing or null bound is the default
+    fn eval_slice_bound(
+        &mut self,
+        bound: &Option<Ast>,
+        default: usize,
+        len: usize,
+    ) -> Result<usize, Failure> {
+        let bound = match bound {
+            Some(bound) => self.eval_node(bound)?,
+            None => return Ok(default),
+        };
+        match bound.node {
+            AstNode::INTVAL(val) if val < 0 => Ok(len.saturating_sub(val.unsigned_abs() as usize)),
+            AstNode::INTVAL(val) => Ok((val as usize).min(len)),
+            AstNode::NULLVAL => Ok(default),
+            other => Err(EvalErr::Type(format!(
+                "slice bound is {}, expect int",
+                other.type_name()
+            ))
+            .into()),
+        }
+    }
+
+    fn eval_var(&mut self, name: &str) -> Result<Ast, Failure> {
+        if let Some((_, val)) = self.locals.iter().rev().find(|(var, _)| var == name) {
+            return Ok(val.clone());
+        }
+        if let Some(val) = self.values.get(name) {
+            return Ok(val.clone());
+        }
+        let name = name.to_string();
+        if let Some(pos) = self.evaluating.iter().position(|var| var == &name) {
+            let mut names = self.evaluating[pos..].to_vec();
+            names.push(name);
+            return Err(EvalErr::Cycle(names).into());
+        }
+        let symbol = match self.ctx.find(&name).or_else(|| self.tab.find(&name)) {
+            Some(symbol) => symbol,
+            None => return Err(EvalErr::Undefined(name).into()),
+        };
+        let expr = match symbol {
+            Symbol::EXPR(expr) => expr,
+            Symbol::FUNC { .. } => {
+                return Err(EvalErr::Type(format!("{} is a function", name)).into())
+            }
+        };
+
+        // symbols do not see comprehension variables at the reference site
+        let locals = std::mem::take(&mut self.locals);
+        self.evaluating.push(name.clone());
+        // a failed definition is kept as its error value, every reference passes it on
+        let val = self.eval_value(expr);
+        self.evaluating.pop();
+        self.locals = locals;
+
+        self.values.insert(name, val.clone());
+        Ok(val)
+    }
+
+    fn eval_func(&mut self, name: &str, params: &[Ast], span: &Span) -> Result<Ast, Failure> {
+        let func_err = |reason: String| EvalErr::Func {
+            name: String::from(name),
+            reason,
+        };
+        match name {
+            "select" | "project" => {
+                builtins::check_arg_count(params, 2, 2).map_err(func_err)?;
+                self.eval_select_project(name, &params[0], &params[1], span)
+            }
+            "template" => {
+                builtins::check_arg_count(params, 1, 2).map_err(func_err)?;
+                let template = match self.eval_node(&params[0])?.node {
+                    AstNode::STRVAL(val) => val,
+                    other => {
+                        return Err(func_err(format!(
+                            "argument 1 should be string, got {}",
+                            other.type_name()
+                        ))
+                        .into())
+                    }
+                };
+                let overrides = match params.get(1) {
+                    Some(param) => match self.eval_node(param)?.node {
+                        AstNode::OBJECT(keyval_pairs) => keyval_pairs,
+                        other => {
+                            return Err(func_err(format!(
+                                "argument 2 should be object, got {}",
+                                other.type_name()
+                            ))
+                            .into())
+                        }
+                    },
+                    None => ObjectMap::new(),
+                };
+                let val = self.eval_template(&template, &overrides)?;
+                Ok(AstNode::STRVAL(val).at(span.clone()))
+            }
+            _ => {
+                let mut args = vec![];
+                for param in params {
+                    args.push(self.eval_node(param)?);
+                }
+                Ok(builtins::call(name, args, span)?)
+            }
+        }
+    }
+
+    /// select keeps the objects for which expr is true, project maps the objects to expr.
+    /// the fields of each object are visible to expr as variables
+    fn eval_select_project(
+        &mut self,
+        name: &str,
+        expr: &Spanned<AstNode>,
+        list: &Spanned<AstNode>,
+        span: &Span,
+    ) -> Result<Ast, Failure> {
+        let func_err = |reason: String| EvalErr::Func {
+            name: String::from(name),
+            reason,
+        };
+        let items = match self.eval_node(list)?.node {
+            AstNode::LIST(items) => items,
+            other => {
+                return Err(func_err(format!(
+                    "argument 2 should be list, got {}",
+                    other.type_name()
+                ))
+                .into())
+            }
+        };
+        let mut result = vec![];
+        for item in items {
+            let fields = match &item.node {
+                AstNode::OBJECT(keyval_pairs) => keyval_pairs.clone(),
+                other => {
+                    return Err(func_err(format!(
+                        "list item should be object, got {}",
+                        other.type_name()
+                    ))
+                    .into())
+                }
+            };
+            let depth = self.locals.len();
+            self.locals.extend(fields);
+            let val = self.eval_node(expr);
+            self.locals.truncate(depth);
+            let val = val?;
+            if name == "project" {
+                result.push(val);
+                continue;
+            }
+            match val.node {
+                AstNode::BOOLVAL(true) => result.push(item),
+                AstNode::BOOLVAL(false) => (),
+                other => {
+                    return Err(func_err(format!(
+                        "{} is {}, expect boolean",
+                        expr,
+                        other.type_name()
+                    ))
+                    .into())
+                }
+            }
+        }
+        Ok(AstNode::LIST(result).at(span.clone()))
+    }
+
+    /// replace {NAME} in the template with the value from overrides or the variable NAME
+    fn eval_template(&mut self, template: &str, overrides: &ObjectMap) -> Result<String, Failure> {
+        let template_err = |reason: String| -> Failure {
+            EvalErr::Func {
+                name: String::from("template"),
+                reason,
+            }
+            .into()
+        };
+        let mut output = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(offset) => start + offset,
+                None => return Err(template_err(String::from("unterminated { in template"))),
+            };
+            let name = &rest[start + 1..end];
+            let val = match overrides.get(name) {
+                Some(val) => val.clone(),
+                None => match self.eval_var(name) {
+                    Ok(val) => val,
+                    Err(Failure::Raised(err)) => return Err(template_err(err.to_string())),
+                    Err(failure) => return Err(failure),
+                },
+            };
+            match &val.node {
+                AstNode::STRVAL(val) => output.push_str(val),
+                AstNode::INTVAL(val) => output.push_str(&val.to_string()),
+                AstNode::DOUBLEVAL(val) => output.push_str(&val.to_string()),
+                AstNode::ERROR(_) => return Err(Failure::Propagated(val)),
+                other => {
+                    return Err(template_err(format!(
+                        "cannot substitute {} into template, got {}",
+                        name,
+                        other.type_name()
+                    )))
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn eval_compre(&mut self, node: &AstNode) -> Result<Vec<Ast>, Failure> {
+        let (expr, clauses) = match node {
+            AstNode::COMPRE { expr, clauses } => (expr, clauses),
+            _ => panic!("not a list comprehension"),
+        };
+        let mut result = vec![];
+        self.eval_compre_clauses(expr, clauses, &mut result)?;
+        first_error(&result)?;
+        Ok(result)
+    }
+
+    /// expand the first clause, the rest are expanded for every item of the first one
+    fn eval_compre_clauses(
+        &mut self,
+        expr: &Spanned<AstNode>,
+        clauses: &[CompreClause],
+        result: &mut Vec<Ast>,
+    ) -> Result<(), Failure> {
+        let (clause, rest) = match clauses.split_first() {
+            Some(split) => split,
+            None => {
+                // an error in one item does not stop the others
+                result.push(self.eval_value(expr));
+                return Ok(());
+            }
+        };
+        let items = match self.eval_node(&clause.iter_expr)?.node {
+            AstNode::LIST(items) => items,
+            other => {
+                return Err(EvalErr::Type(format!(
+                    "cannot iterate over {} in list comprehension",
+                    other.type_name()
+                ))
+                .into())
+            }
+        };
+
+        for item in items {
+            // loop variable is visible to the conditions, inner clauses and expr
+            self.locals.push((clause.var.clone(), item));
+            let expanded = self.eval_compre_item(expr, clause, rest, result);
+            self.locals.pop();
+            expanded?;
+        }
+        Ok(())
+    }
+
+    fn eval_compre_item(
+        &mut self,
+        expr: &Spanned<AstNode>,
+        clause: &CompreClause,
+        rest: &[CompreClause],
+        result: &mut Vec<Ast>,
+    ) -> Result<(), Failure> {
+        for cond in &clause.conds {
+            match self.eval_node(cond)?.node {
+                AstNode::BOOLVAL(true) => (),
+                AstNode::BOOLVAL(false) => return Ok(()),
+                other => {
+                    return Err(EvalErr::Type(format!(
+                        "list comprehension condition {} is {}, expect boolean",
+                        cond,
+                        other.type_name()
+                    ))
+                    .into())
+                }
+            }
+        }
+        self.eval_compre_clauses(expr, rest, result)
+    }
+}
+
+/// a list or object with an error value in it is the first such error
+fn first_error<'v>(items: impl IntoIterator<Item = &'v Ast>) -> Result<(), Failure> {
+    match items.into_iter().find(|item| item.is_error()) {
+        Some(err) => Err(Failure::Propagated(err.clone())),
+        None => Ok(()),
+    }
+}
+
+fn eval_binary(op: &AstNode, left: &AstNode, right: &AstNode) -> Result<AstNode, EvalErr> {
+    // null in arithmetic propagates, e.g. null + 1 is null
+    let is_arith = matches!(
+        op,
+        AstNode::ADD { .. }
+            | AstNode::SUB { .. }
+            | AstNode::MUL { .. }
+            | AstNode::DIV { .. }
+            | AstNode::MOD { .. }
+    );
+    if is_arith && (left.is_null() || right.is_null()) {
+        return Ok(AstNode::NULLVAL);
+    }
+    let val = match op {
+        AstNode::ADD { .. } => match (left, right) {
+            (AstNode::STRVAL(l), AstNode::STRVAL(r)) => AstNode::STRVAL(format!("{}{}", l, r)),
+            (AstNode::LIST(l), AstNode::LIST(r)) => {
+                AstNode::LIST(l.iter().chain(r.iter()).cloned().collect())
+            }
+            (AstNode::OBJECT(l), AstNode::OBJECT(r)) => {
+                // keys from the right operand win
+                let mut merged = l.clone();
+                for (key, value) in r {
+                    merged.insert(key.clone(), value.clone());
+                }
+                AstNode::OBJECT(merged)
+            }
+            _ => eval_arith(op, left, right, i64::checked_add, |l, r| l + r)?,
+        },
+        AstNode::SUB { .. } => eval_arith(op, left, right, i64::checked_sub, |l, r| l - r)?,
+        AstNode::MUL { .. } => eval_arith(op, left, right, i64::checked_mul, |l, r| l * r)?,
+        AstNode::DIV { .. } => {
+            check_divisor(right)?;
+            eval_arith(op, left, right, i64::checked_div, |l, r| l / r)?
+        }
+        AstNode::MOD { .. } => {
+            check_divisor(right)?;
+            eval_arith(op, left, right, i64::checked_rem, |l, r| l % r)?
+        }
+        AstNode::AND { .. } | AstNode::OR { .. } => match (left, right) {
+            (AstNode::BOOLVAL(l), AstNode::BOOLVAL(r)) => match op {
+                AstNode::AND { .. } => AstNode::BOOLVAL(*l && *r),
+                _ => AstNode::BOOLVAL(*l || *r),
+            },
+            _ => return Err(type_err(op, left, right)),
+        },
+        AstNode::EQ { .. } => AstNode::BOOLVAL(values_equal(left, right)),
+        AstNode::NE { .. } => AstNode::BOOLVAL(!values_equal(left, right)),
+        AstNode::LT { .. } | AstNode::LE { .. } | AstNode::GT { .. } | AstNode::GE { .. } => {
+            let ordering = match (left, right) {
+                (AstNode::STRVAL(l), AstNode::STRVAL(r)) => l.partial_cmp(r),
+                _ => match (as_double(left), as_double(right)) {
+                    (Some(l), Some(r)) => l.partial_cmp(&r),
+                    _ => return Err(type_err(op, left, right)),
+                },
+            };
+            let ordering = match ordering {
+                Some(ordering) => ordering,
+                None => return Err(type_err(op, left, right)),
+            };
+            AstNode::BOOLVAL(match op {
+                AstNode::LT { .. } => ordering.is_lt(),
+                AstNode::LE { .. } => ordering.is_le(),
+                AstNode::GT { .. } => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+        _ => panic!("not a binary operator"),
+    };
+    Ok(val)
+}
+
+/// int op int stays int, a double on either side promotes the other side to double
+fn eval_arith(
+    op: &AstNode,
+    left: &AstNode,
+    right: &AstNode,
+    int_op: fn(i64, i64) -> Option<i64>,
+    double_op: fn(f64, f64) -> f64,
+) -> Result<AstNode, EvalErr> {
+    if let (AstNode::INTVAL(l), AstNode::INTVAL(r)) = (left, right) {
+        return match int_op(*l, *r) {
+            Some(val) => Ok(AstNode::INTVAL(val)),
+            None => Err(EvalErr::Arith(format!(
+                "integer overflow in {} {} {}",
+                l,
+                op.operator_str(),
+                r
+            ))),
+        };
+    }
+    match (as_double(left), as_double(right)) {
+        (Some(l), Some(r)) => Ok(AstNode::DOUBLEVAL(double_op(l, r))),
+        _ => Err(type_err(op, left, right)),
+    }
+}
+
+fn check_divisor(divisor: &AstNode) -> Result<(), EvalErr> {
+    if as_double(divisor) == Some(0.0) {
+        return Err(EvalErr::Arith(String::from("division by zero")));
+    }
+    Ok(())
+}
+
+fn as_double(node: &AstNode) -> Option<f64> {
+    match node {
+        AstNode::INTVAL(val) => Some(*val as f64),
+        AstNode::DOUBLEVAL(val) => Some(*val),
+        _ => None,
+    }
+}
+
+/// deep equality, int and double compare by value
+fn values_equal(left: &AstNode, right: &AstNode) -> bool {
+    match (left, right) {
+        (AstNode::STRVAL(l), AstNode::STRVAL(r)) => l == r,
+        (AstNode::BOOLVAL(l), AstNode::BOOLVAL(r)) => l == r,
+        (AstNode::NULLVAL, AstNode::NULLVAL) => true,
+        (AstNode::LIST(l), AstNode::LIST(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| values_equal(l, r))
+        }
+        (AstNode::OBJECT(l), AstNode::OBJECT(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .all(|(key, l)| r.get(key).is_some_and(|r| values_equal(l, r)))
+        }
+        _ => match (as_double(left), as_double(right)) {
+            (Some(l), Some(r)) => l == r,
+            _ => false,
+        },
+    }
+}
+
+/// list[index] with negative index counting from the end, or object[key]
+fn eval_lookup(container: &AstNode, index: &AstNode) -> Result<AstNode, EvalErr> {
+    match (container, index) {
+        (AstNode::LIST(list), AstNode::INTVAL(i)) => {
+            let pos = if *i < 0 {
+                (list.len() as i64).checked_add(*i)
+            } else {
+                Some(*i)
+            };
+            match pos.and_then(|pos| list.get(usize::try_from(pos).ok()?)) {
+                Some(elem) => Ok(elem.node.clone()),
+                None => Err(EvalErr::Lookup(format!(
+                    "index {} out of range for list of length {}",
+                    i,
+                    list.len()
+                ))),
+            }
+        }
+        (AstNode::OBJECT(keyval_pairs), AstNode::STRVAL(key)) => match keyval_pairs.get(key) {
+            Some(value) => Ok(value.node.clone()),
+            None => Err(EvalErr::Lookup(format!("key \"{}\" not in object", key))),
+        },
+        (AstNode::LIST(_), other) => Err(EvalErr::Type(format!(
+            "list index is {}, expect int",
+            other.type_name()
+        ))),
+        (AstNode::OBJECT(_), other) => Err(EvalErr::Type(format!(
+            "object key is {}, expect string",
+            other.type_name()
+        ))),
+        (other, _) => Err(EvalErr::Type(format!(
+            "cannot index {}, expect list or object",
+            other.type_name()
+        ))),
+    }
+}
+
+fn type_err(op: &AstNode, left: &AstNode, right: &AstNode) -> EvalErr {
+    EvalErr::Type(format!(
+        "unsupported operand types for {}: {} and {}",
+        op.operator_str(),
+        left.type_name(),
+        right.type_name()
+    ))
+}
@@ -0,0 +1,97 @@
+use super::ast::AstNode;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum JsonGenErr {
+    // node that has no JSON representation, e.g. variable or operator that is not evaluated
+    Unevaluated(String),
+    // NaN and infinity are not valid JSON numbers
+    InvalidNumber(f64),
+}
+
+impl Error for JsonGenErr {}
+
+impl fmt::Display for JsonGenErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            JsonGenErr::Unevaluated(expr) => {
+                write!(
+                    f,
+                    "cannot generate JSON from unevaluated expression {}",
+                    expr
+                )
+            }
+            JsonGenErr::InvalidNumber(val) => write!(f, "{} is not a valid JSON number", val),
+        }
+    }
+}
+
+/// generate JSON document from an evaluated AST
+pub fn gen_json(node: &AstNode) -> Result<String, JsonGenErr> {
+    let mut output = String::new();
+    gen_node(node, &mut output)?;
+    Ok(output)
+}
+
+fn gen_node(node: &AstNode, output: &mut String) -> Result<(), JsonGenErr> {
+    match node {
+        AstNode::INTVAL(val) => output.push_str(&val.to_string()),
+        AstNode::DOUBLEVAL(val) => gen_double(*val, output)?,
+        AstNode::STRVAL(val) => gen_str(val, output),
+        AstNode::BOOLVAL(val) => output.push_str(&val.to_string()),
+        AstNode::NULLVAL => output.push_str("null"),
+        AstNode::OBJECT(keyval_pairs) => {
+            output.push('{');
+            for (i, (key, value)) in keyval_pairs.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                gen_str(key, output);
+                output.push(':');
+                gen_node(value, output)?;
+            }
+            output.push('}');
+        }
+        AstNode::LIST(list) => {
+            output.push('[');
+            for (i, elem) in list.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                gen_node(elem, output)?;
+            }
+            output.push(']');
+        }
+        _ => return Err(JsonGenErr::Unevaluated(node.to_string())),
+    };
+    Ok(())
+}
+
+fn gen_double(val: f64, output: &mut String) -> Result<(), JsonGenErr> {
+    if !val.is_finite() {
+        return Err(JsonGenErr::InvalidNumber(val));
+    }
+    // Debug format keeps the fractional part (1.0 instead of 1), so the value stays a double
+    output.push_str(&format!("{:?}", val));
+    Ok(())
+}
+
+/// quote and escape a string as JSON string literal
+pub fn gen_str(val: &str, output: &mut String) {
+    output.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0c}' => output.push_str("\\f"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
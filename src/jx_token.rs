@@ -1,6 +1,7 @@
+use super::span::Spanned;
 use std::fmt;
 
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum Token {
     LBRAC,   // {
     RBRAC,   // }
@@ -10,14 +11,16 @@ pub enum Token {
     RPAREN,  // )
     COLON,   // :
     COMMA,   // ,
-    INTCONST(i32),
+    DOT,     // ., attribute access
+    INTCONST(i64),
     DOUBLECONST(f64),
     BOOLCONST(bool),
     STRCONST(String),
     NULL,
     FOR,        // for keyword, used in list comprehension
     IN,         // in keyword, used in list comprehension
-    IF,         // if keyword, used in list comprehension
+    IF,         // if keyword, used in list comprehension and conditional
+    ELSE,       // else keyword, used in conditional
     ADD,        // +
     MINUS,      // -
     MUL,        // *
@@ -33,9 +36,50 @@ pub enum Token {
     GT,         // >
     GE,         // >=
     ID(String), // identifier
+    // comment including its delimiters, only produced when the scanner keeps trivia
+    COMMENT(String),
 }
 
 impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::LBRAC => TokenKind::LBRAC,
+            Token::RBRAC => TokenKind::RBRAC,
+            Token::LSQBRAC => TokenKind::LSQBRAC,
+            Token::RSQBRAC => TokenKind::RSQBRAC,
+            Token::LPAREN => TokenKind::LPAREN,
+            Token::RPAREN => TokenKind::RPAREN,
+            Token::COLON => TokenKind::COLON,
+            Token::COMMA => TokenKind::COMMA,
+            Token::DOT => TokenKind::DOT,
+            Token::INTCONST(_) => TokenKind::INTCONST,
+            Token::DOUBLECONST(_) => TokenKind::DOUBLECONST,
+            Token::BOOLCONST(_) => TokenKind::BOOLCONST,
+            Token::STRCONST(_) => TokenKind::STRCONST,
+            Token::NULL => TokenKind::NULL,
+            Token::FOR => TokenKind::FOR,
+            Token::IN => TokenKind::IN,
+            Token::IF => TokenKind::IF,
+            Token::ELSE => TokenKind::ELSE,
+            Token::ADD => TokenKind::ADD,
+            Token::MINUS => TokenKind::MINUS,
+            Token::MUL => TokenKind::MUL,
+            Token::DIV => TokenKind::DIV,
+            Token::MOD => TokenKind::MOD,
+            Token::NOT => TokenKind::NOT,
+            Token::AND => TokenKind::AND,
+            Token::OR => TokenKind::OR,
+            Token::EQ => TokenKind::EQ,
+            Token::NE => TokenKind::NE,
+            Token::LT => TokenKind::LT,
+            Token::LE => TokenKind::LE,
+            Token::GT => TokenKind::GT,
+            Token::GE => TokenKind::GE,
+            Token::ID(_) => TokenKind::ID,
+            Token::COMMENT(_) => TokenKind::COMMENT,
+        }
+    }
+
     pub fn to_str(&self) -> String {
         match self {
             Token::LBRAC => String::from("{"),
@@ -46,6 +90,7 @@ impl Token {
             Token::RPAREN => String::from(")"),
             Token::COLON => String::from(":"),
             Token::COMMA => String::from(","),
+            Token::DOT => String::from("."),
             Token::INTCONST(i) => i.to_string(),
             Token::DOUBLECONST(f) => f.to_string(),
             Token::BOOLCONST(b) => b.to_string(),
@@ -54,6 +99,7 @@ impl Token {
             Token::FOR => String::from("for"),
             Token::IN => String::from("in"),
             Token::IF => String::from("if"),
+            Token::ELSE => String::from("else"),
             Token::ADD => String::from("+"),
             Token::MINUS => String::from("-"),
             Token::MUL => String::from("*"),
@@ -69,6 +115,7 @@ impl Token {
             Token::GT => String::from(">"),
             Token::GE => String::from(">="),
             Token::ID(name) => String::from(name),
+            Token::COMMENT(text) => String::from(text),
         }
     }
 }
@@ -84,6 +131,7 @@ impl fmt::Display for Token {
             Token::RPAREN => write!(f, "RPAREN"),
             Token::COLON => write!(f, "COLON"),
             Token::COMMA => write!(f, "COMMA"),
+            Token::DOT => write!(f, "DOT"),
             Token::INTCONST(val) => write!(f, "INTCONST({})", val),
             Token::DOUBLECONST(val) => write!(f, "DOUBLECONST({})", val),
             Token::BOOLCONST(val) => write!(f, "  BOOLCONST({})", val),
@@ -92,6 +140,7 @@ impl fmt::Display for Token {
             Token::FOR => write!(f, "FOR"),
             Token::IN => write!(f, "IN"),
             Token::IF => write!(f, "IF"),
+            Token::ELSE => write!(f, "ELSE"),
             Token::ADD => write!(f, "ADD"),
             Token::MINUS => write!(f, "MINUS"),
             Token::MUL => write!(f, "MUL"),
@@ -107,6 +156,90 @@ impl fmt::Display for Token {
             Token::GT => write!(f, "GT"),
             Token::GE => write!(f, "GE"),
             Token::ID(name) => write!(f, "ID({})", name),
+            Token::COMMENT(text) => write!(f, "COMMENT({})", text),
+        }
+    }
+}
+
+/// token with its location in the input
+pub type SpannedToken = Spanned<Token>;
+
+/// token type without value, e.g. the set of tokens expected by the parser
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TokenKind {
+    LBRAC,
+    RBRAC,
+    LSQBRAC,
+    RSQBRAC,
+    LPAREN,
+    RPAREN,
+    COLON,
+    COMMA,
+    DOT,
+    INTCONST,
+    DOUBLECONST,
+    BOOLCONST,
+    STRCONST,
+    NULL,
+    FOR,
+    IN,
+    IF,
+    ELSE,
+    ADD,
+    MINUS,
+    MUL,
+    DIV,
+    MOD,
+    NOT,
+    AND,
+    OR,
+    EQ,
+    NE,
+    LT,
+    LE,
+    GT,
+    GE,
+    ID,
+    COMMENT,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::LBRAC => write!(f, "`{{`"),
+            TokenKind::RBRAC => write!(f, "`}}`"),
+            TokenKind::LSQBRAC => write!(f, "`[`"),
+            TokenKind::RSQBRAC => write!(f, "`]`"),
+            TokenKind::LPAREN => write!(f, "`(`"),
+            TokenKind::RPAREN => write!(f, "`)`"),
+            TokenKind::COLON => write!(f, "`:`"),
+            TokenKind::COMMA => write!(f, "`,`"),
+            TokenKind::DOT => write!(f, "`.`"),
+            TokenKind::INTCONST => write!(f, "integer"),
+            TokenKind::DOUBLECONST => write!(f, "double"),
+            TokenKind::BOOLCONST => write!(f, "boolean"),
+            TokenKind::STRCONST => write!(f, "string"),
+            TokenKind::NULL => write!(f, "`null`"),
+            TokenKind::FOR => write!(f, "`for`"),
+            TokenKind::IN => write!(f, "`in`"),
+            TokenKind::IF => write!(f, "`if`"),
+            TokenKind::ELSE => write!(f, "`else`"),
+            TokenKind::ADD => write!(f, "`+`"),
+            TokenKind::MINUS => write!(f, "`-`"),
+            TokenKind::MUL => write!(f, "`*`"),
+            TokenKind::DIV => write!(f, "`/`"),
+            TokenKind::MOD => write!(f, "`%`"),
+            TokenKind::NOT => write!(f, "`not`"),
+            TokenKind::AND => write!(f, "`and`"),
+            TokenKind::OR => write!(f, "`or`"),
+            TokenKind::EQ => write!(f, "`==`"),
+            TokenKind::NE => write!(f, "`!=`"),
+            TokenKind::LT => write!(f, "`<`"),
+            TokenKind::LE => write!(f, "`<=`"),
+            TokenKind::GT => write!(f, "`>`"),
+            TokenKind::GE => write!(f, "`>=`"),
+            TokenKind::ID => write!(f, "identifier"),
+            TokenKind::COMMENT => write!(f, "comment"),
         }
     }
 }
@@ -1,5 +1,12 @@
 pub mod ast;
+pub mod builtins;
+pub mod eval;
+pub mod json_gen;
 pub mod jx_token;
+pub mod makeflow_gen;
 pub mod parser;
+mod regex;
 pub mod scanner;
+pub mod span;
 pub mod symbol_tab;
+pub mod workflow;
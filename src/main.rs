@@ -1,51 +1,237 @@
-mod ast;
-mod jx_token;
-mod parser;
-mod scanner;
-mod symbol_tab;
+use jx2json::jx_token::SpannedToken;
+use jx2json::parser::{DuplicateKeys, ParseOptions};
+use jx2json::scanner::{ScanOptions, ScannerErr};
+use jx2json::symbol_tab::{Symbol, SymbolTable};
+use jx2json::workflow::{self, Workflow, WorkflowErr};
+use jx2json::{ast, eval, json_gen, makeflow_gen, parser, scanner};
 
 use std::{env, process::exit};
 
 fn main() {
     let cli_args = parse_args();
-    let tokens = match scanner::scan_file(&cli_args.filename) {
-        Ok(tokens) => tokens,
-        Err(err) => {
-            eprintln!("{}", err);
-            exit(-1);
-        }
+    let options = ScanOptions {
+        c_comments: cli_args.c_comments,
+        // comments are only shown, the parser skips them
+        keep_comments: cli_args.print_tokens,
+        dot_access: cli_args.dot_access,
     };
+    let tokens = scan_file(&cli_args.filename, options);
 
     // print tokens
     if cli_args.print_tokens {
         for t in &tokens {
-            println!("{}", t);
+            println!("{}\t{}", t.span, t);
         }
     }
 
-    let parser_result = match parser::parse_tokens(tokens) {
-        Ok(result) => result,
-        Err(err) => {
-            eprintln!("{}", err);
+    // report every error in the file, not only the first one
+    let options = ParseOptions {
+        duplicate_keys: cli_args.duplicate_keys,
+    };
+    let (parser_result, errors) = parser::parse_tokens_recover(tokens, options);
+    if let Some(result) = &parser_result {
+        for warning in &result.warnings {
+            eprint!("warning: {}", warning.render());
+        }
+    }
+    for err in &errors {
+        eprint!("{}", err.render());
+    }
+    if cli_args.check {
+        if !errors.is_empty() {
+            eprintln!("{}: {} error(s)", cli_args.filename, errors.len());
             exit(-1);
         }
+        exit(0);
+    }
+    let parser_result = match parser_result {
+        Some(result) if errors.is_empty() => result,
+        _ => exit(-1),
     };
 
-    match parser_result.root.as_ref() {
-        ast::AstNode::OBJECT(_) => {
+    match &parser_result.root.node {
+        ast::AstNode::OBJECT(_) | ast::AstNode::OBJEXPR(_) => {
             if cli_args.print_ast {
                 println!("{}", parser_result.root);
             }
         }
-        _ => eprintln!("ast root is not not object"),
+        _ => {
+            eprintln!("ast root is not not object");
+            exit(-1);
+        }
+    }
+
+    let ctx = build_ctx(&cli_args);
+    let options = eval::EvalOptions {
+        duplicate_keys: cli_args.duplicate_keys,
+    };
+    let evaluated = eval::eval_all(&parser_result, &ctx, options);
+    for warning in &evaluated.warnings {
+        eprint!("warning: ");
+        report_eval_error(warning);
+    }
+    if evaluated.root.is_error() {
+        // the document failed with the first error, the others are only shown on request
+        if cli_args.all_errors {
+            for err in &evaluated.errors {
+                report_eval_error(err);
+            }
+            eprintln!("{}: {} error(s)", cli_args.filename, evaluated.errors.len());
+        } else {
+            report_eval_error(&evaluated.root);
+        }
+        exit(-1);
+    }
+    let mut root = evaluated.root;
+
+    if cli_args.to_makeflow {
+        let workflow = match Workflow::from_ast(&root) {
+            Ok(workflow) => workflow,
+            Err(errors) => report_workflow_errors(&cli_args.filename, &errors),
+        };
+        match makeflow_gen::gen_makeflow(&workflow) {
+            Ok(makeflow) => print!("{}", makeflow),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(-1);
+            }
+        }
+        return;
+    }
+
+    if cli_args.validate {
+        let errors = workflow::validate(&root);
+        if !errors.is_empty() {
+            report_workflow_errors(&cli_args.filename, &errors);
+        }
+    }
+
+    // keys are in source order unless sorted
+    if cli_args.sort_keys {
+        root.sort_keys();
+    }
+
+    if cli_args.json_gen {
+        match json_gen::gen_json(&root) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(-1);
+            }
+        }
+    }
+}
+
+/// variables supplied on the command line, they take precedence over "define" in the workflow.
+/// --jx-define takes precedence over --jx-args
+fn build_ctx(cli_args: &CLIArgs) -> SymbolTable {
+    let mut ctx = SymbolTable::new();
+    let options = ScanOptions {
+        c_comments: cli_args.c_comments,
+        dot_access: cli_args.dot_access,
+        ..ScanOptions::default()
+    };
+    for filename in &cli_args.jx_args {
+        load_jx_args(&mut ctx, filename, options);
+    }
+    for definition in &cli_args.jx_defines {
+        load_jx_define(&mut ctx, definition, options);
+    }
+    ctx
+}
+
+// every key in the args file becomes a variable
+fn load_jx_args(ctx: &mut SymbolTable, filename: &String, options: ScanOptions) {
+    let tokens = scan_file(filename, options);
+    let result = match parser::parse_tokens(tokens) {
+        Ok(result) => result,
+        Err(err) => {
+            eprint!("{}", err.render());
+            exit(-1);
+        }
+    };
+    match result.root.node {
+        ast::AstNode::OBJECT(keyval_pairs) => {
+            for (name, expr) in keyval_pairs {
+                ctx.insert(name, Symbol::EXPR(expr));
+            }
+        }
+        _ => {
+            eprintln!("{}: jx args is not object", filename);
+            exit(-1);
+        }
     }
 }
 
+// definition is in the form of NAME=EXPR
+fn load_jx_define(ctx: &mut SymbolTable, definition: &str, options: ScanOptions) {
+    let (name, expr) = match definition.split_once('=') {
+        Some((name, expr)) if !name.trim().is_empty() => (name.trim(), expr),
+        _ => {
+            eprintln!("--jx-define expects NAME=EXPR, got {}", definition);
+            exit(-1);
+        }
+    };
+    let expr = match scanner::scan_with(String::from(expr), "--jx-define", options) {
+        Ok(tokens) => match parser::parse_expr(tokens) {
+            Ok(expr) => expr,
+            Err(err) => {
+                eprint!("{}", err.render());
+                exit(-1);
+            }
+        },
+        Err(err) => {
+            eprint!("{}", err.render());
+            exit(-1);
+        }
+    };
+    ctx.insert(String::from(name), Symbol::EXPR(expr));
+}
+
+fn scan_file(filename: &String, options: ScanOptions) -> Vec<SpannedToken> {
+    match scanner::scan_file(filename, options) {
+        Ok(tokens) => tokens,
+        Err(ScannerErr::IO(err)) => {
+            eprintln!("{}: {}", filename, err);
+            exit(-1);
+        }
+        Err(err) => {
+            eprint!("{}", err.render());
+            exit(-1);
+        }
+    }
+}
+
+/// error value with the source line where it was raised
+fn report_eval_error(val: &ast::Ast) {
+    if let ast::AstNode::ERROR(err) = &val.node {
+        eprint!("{} at {}\n{}", err, val.span, val.span.render());
+    }
+}
+
+fn report_workflow_errors(filename: &str, errors: &[WorkflowErr]) -> ! {
+    for err in errors {
+        eprint!("{}", err.render());
+    }
+    eprintln!("{}: {} error(s)", filename, errors.len());
+    exit(-1);
+}
+
 struct CLIArgs {
     filename: String,
+    jx_args: Vec<String>,
+    jx_defines: Vec<String>,
     print_tokens: bool,
     print_ast: bool,
     json_gen: bool,
+    check: bool,
+    c_comments: bool,
+    dot_access: bool,
+    all_errors: bool,
+    validate: bool,
+    to_makeflow: bool,
+    sort_keys: bool,
+    duplicate_keys: DuplicateKeys,
     debug: bool,
 }
 
@@ -58,17 +244,51 @@ fn parse_args() -> CLIArgs {
     }
     let mut cli_args = CLIArgs {
         filename: String::from(""),
+        jx_args: vec![],
+        jx_defines: vec![],
         print_tokens: false,
         print_ast: false,
         json_gen: true,
+        check: false,
+        c_comments: false,
+        dot_access: false,
+        all_errors: false,
+        validate: false,
+        to_makeflow: false,
+        sort_keys: false,
+        duplicate_keys: DuplicateKeys::ERROR,
         debug: false,
     };
-    for arg in &args[1..] {
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
         match arg.as_str() {
             "--help" | "-h" => print_help(),
+            "--jx-args" => cli_args.jx_args.push(option_value(arg, iter.next())),
+            "--jx-define" => cli_args.jx_defines.push(option_value(arg, iter.next())),
             "--print-tokens" => cli_args.print_tokens = true,
             "--print-ast" => cli_args.print_ast = true,
             "--json-gen" => cli_args.json_gen = true,
+            "--check" => cli_args.check = true,
+            "--c-comments" => cli_args.c_comments = true,
+            "--dot-access" => cli_args.dot_access = true,
+            "--all-errors" => cli_args.all_errors = true,
+            "--validate" => cli_args.validate = true,
+            "--to-makeflow" => cli_args.to_makeflow = true,
+            "--sort-keys" => cli_args.sort_keys = true,
+            "--duplicate-keys" => {
+                cli_args.duplicate_keys = match option_value(arg, iter.next()).as_str() {
+                    "error" => DuplicateKeys::ERROR,
+                    "keep-last" => DuplicateKeys::KEEPLAST,
+                    "keep-first" => DuplicateKeys::KEEPFIRST,
+                    other => {
+                        eprintln!(
+                            "--duplicate-keys expects error, keep-last or keep-first, got {}",
+                            other
+                        );
+                        exit(-1);
+                    }
+                }
+            }
             "--debug" => cli_args.debug = true,
             // filename
             _ => cli_args.filename = arg.clone(),
@@ -81,12 +301,32 @@ fn parse_args() -> CLIArgs {
     cli_args
 }
 
+fn option_value(option: &str, value: Option<&String>) -> String {
+    match value {
+        Some(value) => value.clone(),
+        None => {
+            eprintln!("{} needs a value", option);
+            exit(-1);
+        }
+    }
+}
+
 fn print_help() {
     println!("jx2json [OPTIONS] [jx-filename]");
     println!("options:");
     println!("\t--help|-h");
+    println!("\t--jx-args <file>\tload variables from JSON/JX object in file");
+    println!("\t--jx-define <NAME=EXPR>\tdefine variable, overrides --jx-args");
+    println!("\t--c-comments\t\talso accept // and /* */ comments");
+    println!("\t--dot-access\t\taccept a.b for a[\"b\"]");
     println!("\t--print-tokens");
     println!("\t--print-ast");
     println!("\t--json-gen");
+    println!("\t--duplicate-keys <error|keep-last|keep-first>\tdefault is error");
+    println!("\t--sort-keys\t\tsort object keys instead of keeping source order");
+    println!("\t--check\t\t\tonly parse the file and report all errors");
+    println!("\t--validate\t\tcheck that the result is a Makeflow workflow");
+    println!("\t--to-makeflow\t\twrite the workflow in classic Makeflow syntax instead of JSON");
+    println!("\t--all-errors\t\treport all evaluation errors, not only the first one");
     println!("\t--debug");
 }
@@ -0,0 +1,210 @@
+use super::workflow::{Category, Environment, FileSpec, Job, Resources, Workflow};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum MakeflowGenErr {
+    // rule that the classic format can not express, e.g. sub-workflow
+    Unsupported(String),
+    // file, category or variable name that does not fit in the syntax, e.g. with a space
+    InvalidName(String),
+    // command or value that spans several lines
+    MultiLine(String),
+}
+
+impl Error for MakeflowGenErr {}
+
+impl fmt::Display for MakeflowGenErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            MakeflowGenErr::Unsupported(reason) => write!(f, "{}", reason),
+            MakeflowGenErr::InvalidName(name) => {
+                write!(f, "\"{}\" can not be written in Makeflow syntax", name)
+            }
+            MakeflowGenErr::MultiLine(val) => {
+                write!(f, "\"{}\" spans several lines, expect a single line", val)
+            }
+        }
+    }
+}
+
+/// category of the rules that have none, as in Makeflow
+const DEFAULT_CATEGORY: &str = "default";
+
+/// generate a classic Makeflow file (the Make-like format) from a workflow, commands are
+/// indented with a tab, e.g.
+///
+/// ```text
+/// .MAKEFLOW CATEGORY big
+/// .MAKEFLOW CORES 4
+///
+/// out.txt: in.txt
+///     sim in.txt > out.txt
+/// ```
+pub fn gen_makeflow(workflow: &Workflow) -> Result<String, MakeflowGenErr> {
+    let mut output = String::new();
+    gen_environment(&workflow.environment, &mut output)?;
+    if !workflow.environment.vars.is_empty() {
+        output.push('\n');
+    }
+    for category in &workflow.categories {
+        gen_category(category, &mut output)?;
+    }
+
+    let default_category = workflow
+        .default_category
+        .as_deref()
+        .unwrap_or(DEFAULT_CATEGORY);
+    // the category directive applies to everything that follows it
+    let mut current = workflow.categories.last().map(|c| c.name.clone());
+    // names of the categories so far, a copy for a rule must not reuse one
+    let mut names: HashSet<String> = workflow.categories.iter().map(|c| c.name.clone()).collect();
+    for (i, rule) in workflow.rules.iter().enumerate() {
+        let command = match &rule.job {
+            Job::Command(command) => command,
+            Job::Workflow { path, .. } => {
+                return Err(MakeflowGenErr::Unsupported(format!(
+                    "rule {} runs sub-workflow {}, which classic Makeflow can not express",
+                    i, path
+                )))
+            }
+        };
+        if rule.outputs.is_empty() {
+            return Err(MakeflowGenErr::Unsupported(format!(
+                "rule {} has no outputs, which classic Makeflow can not express",
+                i
+            )));
+        }
+        let mut category = rule
+            .category
+            .as_deref()
+            .unwrap_or(default_category)
+            .to_string();
+        // resources of a rule go to a copy of its category that only the rule uses
+        if rule.resources != Resources::default() {
+            let inherited = workflow
+                .categories
+                .iter()
+                .find(|c| c.name == category)
+                .cloned()
+                .unwrap_or_default();
+            let mut name = format!("{}-{}", category, i);
+            let mut n = 1;
+            while names.contains(&name) {
+                name = format!("{}-{}-{}", category, i, n);
+                n += 1;
+            }
+            names.insert(name.clone());
+            category = name;
+            let own = Category {
+                name: category.clone(),
+                resources: merge_resources(&inherited.resources, &rule.resources),
+                environment: inherited.environment,
+            };
+            gen_category(&own, &mut output)?;
+            current = Some(own.name);
+        }
+        if current.as_ref() != Some(&category) {
+            check_name(&category)?;
+            output.push_str(&format!(".MAKEFLOW CATEGORY {}\n", category));
+            current = Some(category);
+        }
+
+        output.push_str(&gen_files(&rule.outputs)?);
+        output.push(':');
+        if !rule.inputs.is_empty() {
+            output.push(' ');
+            output.push_str(&gen_files(&rule.inputs)?);
+        }
+        output.push_str("\n\t");
+        if rule.local_job {
+            output.push_str("LOCAL ");
+        }
+        // variables of a single rule are set in its own shell
+        for (name, value) in &rule.environment.vars {
+            check_name(name)?;
+            check_line(value)?;
+            output.push_str(&format!("export {}={}; ", name, shell_quote(value)));
+        }
+        check_line(command)?;
+        output.push_str(command);
+        output.push_str("\n\n");
+    }
+    Ok(output)
+}
+
+fn gen_category(category: &Category, output: &mut String) -> Result<(), MakeflowGenErr> {
+    check_name(&category.name)?;
+    output.push_str(&format!(".MAKEFLOW CATEGORY {}\n", category.name));
+    let resources = [
+        ("CORES", category.resources.cores),
+        ("MEMORY", category.resources.memory),
+        ("DISK", category.resources.disk),
+        ("GPUS", category.resources.gpus),
+    ];
+    for (directive, val) in &resources {
+        if let Some(val) = val {
+            output.push_str(&format!(".MAKEFLOW {} {}\n", directive, val));
+        }
+    }
+    // variables after the category directive belong to the category
+    gen_environment(&category.environment, output)?;
+    output.push('\n');
+    Ok(())
+}
+
+fn gen_environment(environment: &Environment, output: &mut String) -> Result<(), MakeflowGenErr> {
+    for (name, value) in &environment.vars {
+        check_name(name)?;
+        check_line(value)?;
+        output.push_str(&format!("export {}={}\n", name, shell_quote(value)));
+    }
+    Ok(())
+}
+
+/// file names separated by space, a file renamed for the task is dag_name->task_name
+fn gen_files(files: &[FileSpec]) -> Result<String, MakeflowGenErr> {
+    let mut names = vec![];
+    for file in files {
+        check_name(&file.dag_name)?;
+        match &file.task_name {
+            Some(task_name) => {
+                check_name(task_name)?;
+                names.push(format!("{}->{}", file.dag_name, task_name));
+            }
+            None => names.push(file.dag_name.clone()),
+        }
+    }
+    Ok(names.join(" "))
+}
+
+fn merge_resources(category: &Resources, rule: &Resources) -> Resources {
+    Resources {
+        cores: rule.cores.or(category.cores),
+        memory: rule.memory.or(category.memory),
+        disk: rule.disk.or(category.disk),
+        gpus: rule.gpus.or(category.gpus),
+    }
+}
+
+/// names are separated by whitespace and end at ':' or '=' in the Makeflow syntax
+fn check_name(name: &str) -> Result<(), MakeflowGenErr> {
+    let invalid = |c: char| c.is_whitespace() || c == ':' || c == '=' || c == '#';
+    if name.is_empty() || name.contains(invalid) || name.contains("->") {
+        return Err(MakeflowGenErr::InvalidName(String::from(name)));
+    }
+    Ok(())
+}
+
+fn check_line(val: &str) -> Result<(), MakeflowGenErr> {
+    if val.contains('\n') {
+        return Err(MakeflowGenErr::MultiLine(String::from(val)));
+    }
+    Ok(())
+}
+
+/// single quote for the shell, as the escape() builtin does
+fn shell_quote(val: &str) -> String {
+    format!("'{}'", val.replace('\'', "'\\''"))
+}
@@ -1,51 +1,313 @@
-use super::ast::AstNode;
-use super::jx_token::Token;
-use super::symbol_tab::SymbolTable;
+use super::ast::{Ast, AstNode, CompreClause};
+use super::jx_token::{SpannedToken, Token, TokenKind};
+use super::span::Span;
+use super::symbol_tab::{Symbol, SymbolTable};
 use std::{collections::HashMap, fmt};
 use std::{error::Error, vec};
 
-type Ast = Box<AstNode>;
-
 pub struct ParserResult {
     pub root: Ast,
     pub tab: SymbolTable,
+    // problems that do not stop parsing, e.g. duplicate keys under a lenient policy
+    pub warnings: Vec<ParserErr>,
+}
+
+/// what to do when a key appears more than once in an object
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DuplicateKeys {
+    ERROR,
+    // warn, the value of the last one wins
+    KEEPLAST,
+    // warn, the value of the first one wins
+    KEEPFIRST,
+}
+
+#[derive(Clone, Copy)]
+pub struct ParseOptions {
+    pub duplicate_keys: DuplicateKeys,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            duplicate_keys: DuplicateKeys::ERROR,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct ParserErr {}
+pub enum ParserErr {
+    // token that can not appear here, context describes where, e.g. "after key"
+    UnexpectedToken {
+        expected: Vec<TokenKind>,
+        found: SpannedToken,
+        context: &'static str,
+    },
+    // input ends while more tokens are expected, span is the end of input
+    UnexpectedEof {
+        expected: Vec<TokenKind>,
+        span: Span,
+        context: &'static str,
+    },
+    // key appears more than once in an object, first is the location of the earlier one
+    DuplicateKey {
+        key: String,
+        span: Span,
+        first: Span,
+    },
+    // malformed list comprehension clause
+    InvalidComprehension {
+        reason: String,
+        span: Span,
+    },
+    // top-level "define" is not an object
+    InvalidDefine {
+        found: String,
+        span: Span,
+    },
+    // lists, objects or expressions nested deeper than the limit, span is where it is exceeded
+    TooDeep {
+        limit: usize,
+        span: Span,
+    },
+}
+
+/// nesting limit, deeper input would overflow the stack of the parser or the evaluator
+pub const MAX_DEPTH: usize = 128;
+
+/// tokens that can start an expression
+pub const EXPR_START: [TokenKind; 11] = [
+    TokenKind::STRCONST,
+    TokenKind::INTCONST,
+    TokenKind::DOUBLECONST,
+    TokenKind::BOOLCONST,
+    TokenKind::NULL,
+    TokenKind::LBRAC,
+    TokenKind::LSQBRAC,
+    TokenKind::LPAREN,
+    TokenKind::ID,
+    TokenKind::MINUS,
+    TokenKind::NOT,
+];
 
 impl Error for ParserErr {}
 
 impl fmt::Display for ParserErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "parser error")
+        match &self {
+            ParserErr::UnexpectedToken {
+                expected,
+                found,
+                context,
+            } => {
+                let found = describe_token(found);
+                if expected.is_empty() {
+                    write!(f, "unexpected {}", found)?;
+                } else {
+                    write!(f, "expected {}", describe_expected(expected))?;
+                }
+                if !context.is_empty() {
+                    write!(f, " {}", context)?;
+                }
+                if !expected.is_empty() {
+                    write!(f, ", found {}", found)?;
+                }
+                write!(f, " at {}", self.span())
+            }
+            ParserErr::UnexpectedEof {
+                expected,
+                span,
+                context,
+            } => {
+                write!(f, "unexpected end of input")?;
+                if !expected.is_empty() {
+                    write!(f, ", expected {}", describe_expected(expected))?;
+                }
+                if !context.is_empty() {
+                    write!(f, " {}", context)?;
+                }
+                write!(f, " at {}", span)
+            }
+            ParserErr::DuplicateKey { key, span, first } => write!(
+                f,
+                "duplicate key \"{}\" at {}, first defined at {}",
+                key, span, first
+            ),
+            ParserErr::InvalidComprehension { reason, span } => {
+                write!(f, "invalid list comprehension, {} at {}", reason, span)
+            }
+            ParserErr::InvalidDefine { found, span } => write!(
+                f,
+                "\"define\" should be an object, found {} at {}",
+                found, span
+            ),
+            ParserErr::TooDeep { limit, span } => {
+                write!(f, "nesting deeper than {} levels at {}", limit, span)
+            }
+        }
     }
 }
 
-pub fn parse_tokens(tokens: Vec<Token>) -> Result<ParserResult, ParserErr> {
-    let mut src = TokenSrc::from(tokens);
-    match parse_workflow(&mut src) {
-        Some(node) => {
-            return Ok(ParserResult {
-                root: node,
-                tab: SymbolTable::new(),
-            })
+impl ParserErr {
+    /// location of the error
+    pub fn span(&self) -> &Span {
+        match &self {
+            ParserErr::UnexpectedToken { found, .. } => &found.span,
+            ParserErr::UnexpectedEof { span, .. }
+            | ParserErr::DuplicateKey { span, .. }
+            | ParserErr::InvalidComprehension { span, .. }
+            | ParserErr::InvalidDefine { span, .. }
+            | ParserErr::TooDeep { span, .. } => span,
         }
-        None => {
-            return Err(ParserErr {});
+    }
+
+    /// error message followed by the source snippet where the error is
+    pub fn render(&self) -> String {
+        match &self {
+            // show both keys
+            ParserErr::DuplicateKey { span, first, .. } => {
+                format!("{}\n{}{}", self, span.render(), first.render())
+            }
+            _ => format!("{}\n{}", self, self.span().render()),
+        }
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::STRCONST(val) => format!("string \"{}\"", val),
+        token => format!("`{}`", token.to_str()),
+    }
+}
+
+fn describe_expected(expected: &[TokenKind]) -> String {
+    if expected == EXPR_START {
+        return String::from("expression");
+    }
+    let kinds: Vec<String> = expected.iter().map(|kind| kind.to_string()).collect();
+    match kinds.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// parse the document, fail with the first error
+pub fn parse_tokens(tokens: Vec<SpannedToken>) -> Result<ParserResult, ParserErr> {
+    let (result, errors) = parse_tokens_recover(tokens, ParseOptions::default());
+    match (result, errors.into_iter().next()) {
+        (Some(result), None) => Ok(result),
+        (_, Some(err)) => Err(err),
+        (None, None) => panic!("parser should report error if there is no result"),
+    }
+}
+
+/// parse the document and report all errors in the order they appear.
+/// after an error, the parser skips to the next `,`, `}` or `]` and continues,
+/// the erroneous key value pair or list element is left out of the partial AST.
+/// result is None if the document is not an object
+pub fn parse_tokens_recover(
+    tokens: Vec<SpannedToken>,
+    options: ParseOptions,
+) -> (Option<ParserResult>, Vec<ParserErr>) {
+    let mut src = TokenSrc::from(tokens);
+    src.options = options;
+    let root = match parse_workflow(&mut src) {
+        Ok(root) => root,
+        Err(err) => return (None, vec![err]),
+    };
+    // all tokens should be consumed
+    if let Err(err) = src.expect_end("after the end of document") {
+        src.report(err);
+    }
+    let tab = match build_symbol_tab(&root) {
+        Ok(tab) => tab,
+        Err(err) => {
+            src.report(err);
+            SymbolTable::new()
         }
+    };
+    let mut errors = src.errors;
+    errors.sort_by_key(|err| err.span().start);
+    let warnings = src.warnings;
+    (
+        Some(ParserResult {
+            root,
+            tab,
+            warnings,
+        }),
+        errors,
+    )
+}
+
+/// parse tokens of a single expression, e.g. expression supplied on the command line
+pub fn parse_expr(tokens: Vec<SpannedToken>) -> Result<Ast, ParserErr> {
+    let mut src = TokenSrc::from(tokens);
+    let expr = match_expr(&mut src)?;
+    src.expect_end("after the end of expression")?;
+    match src.errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(expr),
     }
 }
 
+/// symbols from the top-level "define" object, the expressions are evaluated when referenced
+fn build_symbol_tab(root: &AstNode) -> Result<SymbolTable, ParserErr> {
+    let mut tab = SymbolTable::new();
+    let define = match root {
+        AstNode::OBJECT(keyval_pairs) => keyval_pairs.get("define"),
+        // other keys are computed
+        AstNode::OBJEXPR(pairs) => pairs
+            .iter()
+            .find(|(key, _)| matches!(&key.node, AstNode::STRVAL(key) if key == "define"))
+            .map(|(_, value)| value),
+        _ => None,
+    };
+    let define = match define {
+        Some(define) => define,
+        None => return Ok(tab),
+    };
+    match &define.node {
+        AstNode::OBJECT(definitions) => {
+            for (name, expr) in definitions {
+                tab.insert(name.clone(), Symbol::EXPR(expr.clone()));
+            }
+        }
+        // "define" must be an object
+        other => {
+            return Err(ParserErr::InvalidDefine {
+                found: String::from(other.type_name()),
+                span: define.span.clone(),
+            })
+        }
+    };
+    Ok(tab)
+}
+
 struct TokenSrc {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     curr_index: usize,
+    // errors the parser recovered from
+    errors: Vec<ParserErr>,
+    warnings: Vec<ParserErr>,
+    options: ParseOptions,
+    // levels of nesting being parsed, see nested
+    depth: usize,
 }
 impl TokenSrc {
-    fn from(tokens: Vec<Token>) -> TokenSrc {
+    fn from(tokens: Vec<SpannedToken>) -> TokenSrc {
+        // comments are trivia, the grammar does not see them
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| token.kind() != TokenKind::COMMENT)
+            .collect();
         TokenSrc {
             tokens,
             curr_index: 0,
+            errors: vec![],
+            warnings: vec![],
+            options: ParseOptions::default(),
+            depth: 0,
         }
     }
 
@@ -53,415 +315,470 @@ impl TokenSrc {
         if self.curr_index + 1 > self.tokens.len() {
             panic!("consume out of bound")
         }
-        println!("consume {}", self.tokens[self.curr_index]);
         self.curr_index += 1;
     }
 
-    fn backtrack_one(&mut self) {
-        if self.curr_index < 1 {
-            panic!("backtrack_one out of bound")
+    /// current token, None if all tokens are consumed
+    fn curr(&self) -> Option<&Token> {
+        self.tokens.get(self.curr_index).map(|token| &token.node)
+    }
+
+    /// span of current token, or the end of input if all tokens are consumed
+    fn curr_span(&self) -> Span {
+        match self.tokens.get(self.curr_index) {
+            Some(token) => token.span.clone(),
+            None => match self.tokens.last() {
+                Some(token) => token.span.end_point(),
+                None => Span::default(),
+            },
         }
-        self.curr_index -= 1;
     }
 
-    fn backtrack_to(&mut self, index: usize) {
-        if self.curr_index < index {
-            panic!("backtrack_to cannot be used to advance")
+    /// span from start to the end of the last consumed token
+    fn span_from(&self, start: &Span) -> Span {
+        match self.curr_index.checked_sub(1) {
+            Some(prev) => start.to(&self.tokens[prev].span),
+            None => start.clone(),
         }
-        self.curr_index = index;
     }
 
-    fn curr(&self) -> &Token {
-        self.tokens.get(self.curr_index).unwrap()
+    /// error for current token, which is none of the expected
+    fn unexpected(&self, expected: &[TokenKind], context: &'static str) -> ParserErr {
+        match self.tokens.get(self.curr_index) {
+            Some(found) => ParserErr::UnexpectedToken {
+                expected: expected.to_vec(),
+                found: found.clone(),
+                context,
+            },
+            None => ParserErr::UnexpectedEof {
+                expected: expected.to_vec(),
+                span: self.curr_span(),
+                context,
+            },
+        }
     }
 
-    fn curr_token(&self) -> Token {
-        self.tokens.get(self.curr_index).unwrap().clone()
+    /// consume the token of the kind and return its span, e.g. expect `:` after key
+    fn expect(&mut self, kind: TokenKind, context: &'static str) -> Result<Span, ParserErr> {
+        let span = self.curr_span();
+        if self.curr().map(Token::kind) == Some(kind) {
+            self.consume();
+            return Ok(span);
+        }
+        Err(self.unexpected(&[kind], context))
     }
 
-    fn next_token(&self) -> Token {
-        self.tokens.get(self.curr_index + 1).unwrap().clone()
+    fn expect_end(&self, context: &'static str) -> Result<(), ParserErr> {
+        match self.curr() {
+            Some(_) => Err(self.unexpected(&[], context)),
+            None => Ok(()),
+        }
     }
 
-    fn curr_index(&self) -> usize {
-        self.curr_index
+    /// parse one level deeper, fail at the current token once nesting exceeds MAX_DEPTH
+    fn nested(
+        &mut self,
+        match_fn: fn(&mut TokenSrc) -> Result<Ast, ParserErr>,
+    ) -> Result<Ast, ParserErr> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParserErr::TooDeep {
+                limit: MAX_DEPTH,
+                span: self.curr_span(),
+            });
+        }
+        self.depth += 1;
+        let expr = match_fn(self);
+        self.depth -= 1;
+        expr
+    }
+
+    /// record an error, unless there is already one at the same place
+    fn report(&mut self, err: ParserErr) {
+        let start = err.span().start;
+        if self.errors.iter().all(|prev| prev.span().start != start) {
+            self.errors.push(err);
+        }
+    }
+
+    /// skip to the next `,`, `}`, `]` or `)` that is not nested in the skipped tokens
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.curr() {
+            match token {
+                Token::COMMA | Token::RBRAC | Token::RSQBRAC | Token::RPAREN if depth == 0 => {
+                    return
+                }
+                Token::LBRAC | Token::LSQBRAC | Token::LPAREN => depth += 1,
+                Token::RBRAC | Token::RSQBRAC | Token::RPAREN if depth > 0 => depth -= 1,
+                _ => (),
+            }
+            self.consume();
+        }
     }
 }
 
-fn parse_workflow(src: &mut TokenSrc) -> Option<Ast> {
+fn parse_workflow(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
     match_object(src)
 }
 
-fn match_key_val_list(
+/// comma separated items up to the close token, which is consumed.
+/// an item with error is skipped, so are tokens up to the next `,` or closing bracket
+fn match_seq<T>(
     src: &mut TokenSrc,
-    mut keyval_pairs: HashMap<String, Ast>,
-) -> Option<HashMap<String, Ast>> {
-    match match_key_val(src) {
-        Some((k, v)) => {
-            keyval_pairs.insert(k, v);
+    close: TokenKind,
+    context: &'static str,
+    match_item: fn(&mut TokenSrc) -> Result<T, ParserErr>,
+) -> Vec<T> {
+    let mut items = vec![];
+    if match_terminal(src, close) {
+        return items;
+    }
+    loop {
+        match match_item(src) {
+            Ok(item) => items.push(item),
+            Err(err) => {
+                src.report(err);
+                src.synchronize();
+            }
+        }
+        loop {
+            if match_terminal(src, TokenKind::COMMA) {
+                break;
+            }
+            if match_terminal(src, close) {
+                return items;
+            }
+            src.report(src.unexpected(&[TokenKind::COMMA, close], context));
+            match src.curr() {
+                // closing bracket of the enclosing object or list, or end of input
+                None | Some(Token::RBRAC) | Some(Token::RSQBRAC) | Some(Token::RPAREN) => {
+                    return items
+                }
+                _ => {
+                    src.consume();
+                    src.synchronize();
+                }
+            }
         }
-        None => return None,
     }
+}
 
-    match src.curr() {
-        // more key_val
-        Token::COMMA => (),
-        _ => return Some(keyval_pairs),
-    }
-    if !match_terminal(src, Token::COMMA) {
-        // key_val
-        return None;
+/// key is any expression, it should evaluate to a string
+fn match_key_val(src: &mut TokenSrc) -> Result<(Ast, Ast), ParserErr> {
+    let key = match_binary_expr(src, 0)?;
+    src.expect(TokenKind::COLON, "after key")?;
+    let value = match_expr(src)?;
+    Ok((key, value))
+}
+
+fn match_expr(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let expr = match_cond_expr(src)?;
+    if src.curr() == Some(&Token::FOR) {
+        return match_list_compre_expr(src, expr);
     }
+    Ok(expr)
+}
 
-    match match_key_val_list(src, keyval_pairs) {
-        Some(pairs) => return Some(pairs),
-        None => return None,
+/// `then if cond else orelse`, binds looser than any binary operator
+fn match_cond_expr(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let then = match_binary_expr(src, 0)?;
+    if !match_terminal(src, TokenKind::IF) {
+        return Ok(then);
     }
+    let cond = match_binary_expr(src, 0)?;
+    src.expect(TokenKind::ELSE, "in conditional")?;
+    let orelse = src.nested(match_cond_expr)?;
+    let span = then.span.to(&orelse.span);
+    Ok(AstNode::IFELSE { cond, then, orelse }.at(span))
 }
 
-fn match_key_val(src: &mut TokenSrc) -> Option<(String, Ast)> {
-    let key = match match_STRVAL(src) {
-        Some(key) => key,
-        None => return None,
-    };
-    if !match_terminal(src, Token::COLON) {
-        return None;
+/// operand of not is made of the operators that bind tighter than this
+const NOT_PRECEDENCE: u8 = 2;
+
+/// precedence of binary operators, higher binds tighter, all of them are left associative
+fn binary_precedence(token: &Token) -> Option<u8> {
+    match token {
+        Token::OR => Some(1),
+        Token::AND => Some(2),
+        Token::EQ | Token::NE | Token::LT | Token::LE | Token::GT | Token::GE => Some(3),
+        Token::ADD | Token::MINUS => Some(4),
+        Token::MUL | Token::DIV | Token::MOD => Some(5),
+        _ => None,
     }
-    let value = match match_expr(src) {
-        Some(val) => val,
-        None => return None,
-    };
-    Some((key, value))
 }
 
-fn match_expr(src: &mut TokenSrc) -> Option<Ast> {
-    match src.curr() {
-        // both jx_expr and value
-        Token::STRCONST(_)
-        | Token::INTCONST(_)
-        | Token::DOUBLECONST(_)
-        | Token::BOOLCONST(_)
-        | Token::LBRAC
-        | Token::LSQBRAC => (),
-        // jx_expr only
-        Token::ID(_) => return match_jx_expr(src),
-        _ => return None,
+/// precedence climbing, only consume operators that bind tighter than min_prec
+fn match_binary_expr(src: &mut TokenSrc, min_prec: u8) -> Result<Ast, ParserErr> {
+    let mut left = match_unary_expr(src)?;
+    loop {
+        let op = match src.curr() {
+            Some(op) => op.clone(),
+            None => return Ok(left),
+        };
+        let prec = match binary_precedence(&op) {
+            Some(prec) if prec > min_prec => prec,
+            _ => return Ok(left),
+        };
+        src.consume();
+        let right = match_binary_expr(src, prec)?;
+        let span = left.span.to(&right.span);
+        left = binary_node(&op, left, right).at(span);
     }
+}
 
-    let index = src.curr_index();
-    match match_jx_expr(src) {
-        Some(expr) => return Some(expr),
-        None => src.backtrack_to(index),
+fn binary_node(op: &Token, left: Ast, right: Ast) -> AstNode {
+    match op {
+        Token::OR => AstNode::OR { left, right },
+        Token::AND => AstNode::AND { left, right },
+        Token::EQ => AstNode::EQ { left, right },
+        Token::NE => AstNode::NE { left, right },
+        Token::LT => AstNode::LT { left, right },
+        Token::LE => AstNode::LE { left, right },
+        Token::GT => AstNode::GT { left, right },
+        Token::GE => AstNode::GE { left, right },
+        Token::ADD => AstNode::ADD { left, right },
+        Token::MINUS => AstNode::SUB { left, right },
+        Token::MUL => AstNode::MUL { left, right },
+        Token::DIV => AstNode::DIV { left, right },
+        Token::MOD => AstNode::MOD { left, right },
+        _ => panic!("{} is not a binary operator", op),
     }
-    println!("backtracked jx_expr");
-    match_value(src)
 }
 
-fn match_value(src: &mut TokenSrc) -> Option<Ast> {
+/// every nested list, object, parenthesis or unary operator is parsed through here
+fn match_unary_expr(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    src.nested(match_prefixed_expr)
+}
+
+fn match_prefixed_expr(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let start = src.curr_span();
     match src.curr() {
-        Token::STRCONST(val) => {
-            let node = Box::new(AstNode::STRVAL(val.clone()));
+        Some(Token::MINUS) => {
             src.consume();
-            Some(node)
+            let expr = match_unary_expr(src)?;
+            let span = start.to(&expr.span);
+            Ok(negate(expr).at(span))
         }
-        Token::INTCONST(val) => {
-            let node = Box::new(AstNode::INTVAL(val.clone()));
+        Some(Token::NOT) => {
             src.consume();
-            Some(node)
+            // not is between and and the comparisons, e.g. not a == b is not (a == b)
+            let expr = match_binary_expr(src, NOT_PRECEDENCE)?;
+            let span = start.to(&expr.span);
+            Ok(AstNode::NOT { expr }.at(span))
         }
-        Token::DOUBLECONST(val) => {
-            let node = Box::new(AstNode::DOUBLEVAL(val.clone()));
-            src.consume();
-            Some(node)
-        }
-        Token::BOOLCONST(val) => {
-            let node = Box::new(AstNode::BOOLVAL(val.clone()));
-            src.consume();
-            Some(node)
+        _ => {
+            let expr = match_primary_expr(src)?;
+            match_postfix_expr(src, expr)
         }
-        Token::LBRAC => return match_object(src),
-        Token::LSQBRAC => return match_list(src),
-        _ => return None,
     }
 }
 
-fn match_object(src: &mut TokenSrc) -> Option<Ast> {
-    if !match_terminal(src, Token::LBRAC) {
-        return None;
+/// any number of `[index]` or `[start:end]` after expr
+fn match_postfix_expr(src: &mut TokenSrc, mut expr: Ast) -> Result<Ast, ParserErr> {
+    loop {
+        let expr_start = expr.span.clone();
+        if match_terminal(src, TokenKind::DOT) {
+            // a.b is a["b"]
+            let index = match src.curr() {
+                Some(Token::ID(name)) => AstNode::STRVAL(name.clone()).at(src.curr_span()),
+                _ => return Err(src.unexpected(&[TokenKind::ID], "after `.`")),
+            };
+            src.consume();
+            expr = AstNode::LOOKUP { expr, index }.at(src.span_from(&expr_start));
+            continue;
+        }
+        if !match_terminal(src, TokenKind::LSQBRAC) {
+            break;
+        }
+        let start = match src.curr() {
+            Some(Token::COLON) => None,
+            _ => Some(match_binary_expr(src, 0)?),
+        };
+        let node = match (start, match_terminal(src, TokenKind::COLON)) {
+            (Some(index), false) => {
+                src.expect(TokenKind::RSQBRAC, "to close index")?;
+                AstNode::LOOKUP { expr, index }
+            }
+            (start, _) => {
+                let end = match src.curr() {
+                    Some(Token::RSQBRAC) => None,
+                    _ => Some(match_binary_expr(src, 0)?),
+                };
+                src.expect(TokenKind::RSQBRAC, "to close slice")?;
+                AstNode::SLICE { expr, start, end }
+            }
+        };
+        expr = node.at(src.span_from(&expr_start));
     }
+    Ok(expr)
+}
 
-    let keyval_pairs: HashMap<String, Box<AstNode>> = HashMap::new();
-    match src.curr() {
-        // empty object
-        Token::RBRAC => return Some(Box::new(AstNode::OBJECT(keyval_pairs))),
-        _ => (),
-    };
-
-    let keyval_pairs = match match_key_val_list(src, keyval_pairs) {
-        Some(keyval_list) => keyval_list,
-        None => return None,
-    };
-
-    let obj = Box::new(AstNode::OBJECT(keyval_pairs));
-    if !match_terminal(src, Token::RBRAC) {
-        return None;
+/// negative number literals are folded into constant, so plain JSON numbers stay values
+fn negate(expr: Ast) -> AstNode {
+    match expr.node {
+        // -i64::MIN overflows, evaluating NEG reports it
+        AstNode::INTVAL(val) if val != i64::MIN => AstNode::INTVAL(-val),
+        AstNode::DOUBLEVAL(val) => AstNode::DOUBLEVAL(-val),
+        _ => AstNode::NEG { expr },
     }
-    Some(obj)
 }
 
-fn match_list(src: &mut TokenSrc) -> Option<Ast> {
-    let list;
-
-    if !match_terminal(src, Token::LSQBRAC) {
-        return None;
-    }
+fn match_primary_expr(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
     match src.curr() {
-        // empty list
-        Token::RSQBRAC => list = Box::new(AstNode::LIST(vec![])),
-        // non-empty list
-        _ => {
-            let expr_list = vec![];
-            let expr_list = match match_expr_list(src, expr_list) {
-                Some(expr_list) => expr_list,
-                None => return None,
-            };
-            list = Box::new(AstNode::LIST(expr_list));
+        Some(Token::ID(_)) => {
+            let id = match_id(src)?;
+            match (&id.node, src.curr()) {
+                (AstNode::VAR(name), Some(Token::LPAREN)) => {
+                    match_func_call(src, name.clone(), &id.span)
+                }
+                _ => Ok(id),
+            }
         }
-    };
-
-    if !match_terminal(src, Token::RSQBRAC) {
-        return None;
+        Some(Token::LPAREN) => {
+            src.consume();
+            let expr = match_cond_expr(src)?;
+            src.expect(TokenKind::RPAREN, "to close `(`")?;
+            Ok(expr)
+        }
+        _ => match_value(src),
     }
-    Some(list)
 }
 
-fn match_expr_list(src: &mut TokenSrc, mut list: Vec<Ast>) -> Option<Vec<Ast>> {
-    let expr = match match_expr(src) {
-        Some(expr) => expr,
-        None => return None,
+fn match_value(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let node = match src.curr() {
+        Some(Token::STRCONST(val)) => AstNode::STRVAL(val.clone()),
+        Some(Token::INTCONST(val)) => AstNode::INTVAL(*val),
+        Some(Token::DOUBLECONST(val)) => AstNode::DOUBLEVAL(*val),
+        Some(Token::BOOLCONST(val)) => AstNode::BOOLVAL(*val),
+        Some(Token::NULL) => AstNode::NULLVAL,
+        Some(Token::LBRAC) => return match_object(src),
+        Some(Token::LSQBRAC) => return match_list(src),
+        _ => return Err(src.unexpected(&EXPR_START, "")),
     };
-    list.push(expr);
-
-    match src.curr() {
-        // more expr
-        Token::COMMA => (),
-        // no more expr, return the matched one
-        _ => return Some(list),
-    };
-
-    if !match_terminal(src, Token::COMMA) {
-        return None;
-    }
-
-    let list = match match_expr_list(src, list) {
-        Some(list) => list,
-        None => return None,
-    };
-    Some(list)
+    let node = node.at(src.curr_span());
+    src.consume();
+    Ok(node)
 }
 
-fn match_jx_expr(src: &mut TokenSrc) -> Option<Ast> {
-    println!("\t\t{}", src.next_token());
-    println!("jx_expr");
-    match src.curr() {
-        // both jx_arith_expr | list_compre_expr
-        Token::STRCONST(_) | Token::INTCONST(_) | Token::DOUBLECONST(_) => {
-            let index = src.curr_index();
-            match match_jx_arith_expr(src) {
-                Some(ast) => return Some(ast),
-                None => src.backtrack_to(index),
+fn match_object(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let start = src.expect(TokenKind::LBRAC, "to start object")?;
+
+    let mut pairs: Vec<(Ast, Ast)> = vec![];
+    // position of string keys in pairs, computed keys are checked when evaluated
+    let mut key_index: HashMap<String, usize> = HashMap::new();
+    for (key, value) in match_seq(src, TokenKind::RBRAC, "in object", match_key_val) {
+        if let AstNode::STRVAL(name) = &key.node {
+            if let Some(i) = key_index.get(name) {
+                let err = ParserErr::DuplicateKey {
+                    key: name.clone(),
+                    span: key.span.clone(),
+                    first: pairs[*i].0.span.clone(),
+                };
+                match src.options.duplicate_keys {
+                    DuplicateKeys::ERROR => src.report(err),
+                    DuplicateKeys::KEEPFIRST => src.warnings.push(err),
+                    DuplicateKeys::KEEPLAST => {
+                        src.warnings.push(err);
+                        pairs[*i].1 = value;
+                    }
+                }
+                continue;
             }
-            println!("backtrack jx_arith_expr");
-            match_list_compre_expr(src)
+            key_index.insert(name.clone(), pairs.len());
         }
-        // list_compre_expr only
-        Token::BOOLCONST(_) | Token::LBRAC | Token::LSQBRAC => match_list_compre_expr(src),
-        _ => None,
+        pairs.push((key, value));
     }
-}
 
-fn match_jx_arith_expr(src: &mut TokenSrc) -> Option<Ast> {
-    match src.curr() {
-        Token::STRCONST(_) => {
-            let s1 = match match_STRVAL(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            if !match_terminal(src, Token::ADD) {
-                return None;
-            }
-            let s2 = match match_STRVAL(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            Some(Box::new(AstNode::ADD {
-                left: Box::new(AstNode::STRVAL(s1)),
-                right: Box::new(AstNode::STRVAL(s2)),
-            }))
-        }
-        Token::INTCONST(_) => {
-            let i1 = match match_int(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            if !match_terminal(src, Token::ADD) {
-                return None;
-            }
-            let i2 = match match_int(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            Some(Box::new(AstNode::ADD {
-                left: Box::new(AstNode::INTVAL(i1)),
-                right: Box::new(AstNode::INTVAL(i2)),
-            }))
-        }
-        Token::DOUBLECONST(_) => {
-            let d1 = match match_double(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            if !match_terminal(src, Token::ADD) {
-                return None;
-            }
-            let d2 = match match_double(src) {
-                Some(val) => val,
-                None => return None,
-            };
-            return Some(Box::new(AstNode::ADD {
-                left: Box::new(AstNode::DOUBLEVAL(d1)),
-                right: Box::new(AstNode::DOUBLEVAL(d2)),
-            }));
-        }
-        _ => return None,
+    let span = src.span_from(&start);
+    if !pairs.iter().all(|(key, _)| key.is_str()) {
+        return Ok(AstNode::OBJEXPR(pairs).at(span));
     }
+    let keyval_pairs = pairs
+        .into_iter()
+        .map(|(key, value)| match key.node {
+            AstNode::STRVAL(key) => (key, value),
+            _ => panic!("key should be string"),
+        })
+        .collect();
+    Ok(AstNode::OBJECT(keyval_pairs).at(span))
 }
 
-fn match_list_compre_expr(src: &mut TokenSrc) -> Option<Ast> {
-    println!("list_compre_expr");
-    let expr = match match_value(src) {
-        Some(val) => val,
-        None => return None,
-    };
-
-    if !match_terminal(src, Token::FOR) {
-        return None;
-    }
-    let variable = match match_id(src) {
-        Some(val) => val,
-        None => return None,
-    };
-    if !match_terminal(src, Token::IN) {
-        return None;
-    }
-    // iterable_expr
-    let list = match match_list(src) {
-        Some(val) => val,
-        None => return None,
-    };
-    // opt_list_compre_expr
-    // FIXME
-    //match match_opt_list_compre_expr(src) {};
+fn match_list(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
+    let start = src.expect(TokenKind::LSQBRAC, "to start list")?;
+    let list = match_seq(src, TokenKind::RSQBRAC, "in list", match_expr);
+    Ok(AstNode::LIST(list).at(src.span_from(&start)))
+}
 
-    Some(Box::new(AstNode::COMPRE {
-        expr: expr,
-        var: "".to_string(), // FIXME use variable
-        iter_expr: list,
-    }))
+fn match_func_call(src: &mut TokenSrc, name: String, start: &Span) -> Result<Ast, ParserErr> {
+    src.expect(TokenKind::LPAREN, "after function name")?;
+    let params = match_seq(src, TokenKind::RPAREN, "in function call", match_expr);
+    Ok(AstNode::FUNC { name, params }.at(src.span_from(start)))
 }
 
-fn match_opt_list_compre_expr(src: &mut TokenSrc) -> Option<Ast> {
-    if !match_terminal(src, Token::FOR) {
-        return None;
-    }
-    let variable = match match_id(src) {
-        Some(val) => val,
-        None => return None,
-    };
-    if !match_terminal(src, Token::IN) {
-        return None;
-    }
-    // iterable_expr
-    let list = match match_list(src) {
-        Some(val) => val,
-        None => return None,
-    };
+fn match_list_compre_expr(src: &mut TokenSrc, expr: Ast) -> Result<Ast, ParserErr> {
+    let mut clauses = vec![match_compre_clause(src)?];
     // opt_list_compre_expr
-    // FIXME
-    //match match_opt_list_compre_expr(src) {};
-
-    // FIXME
-    None
+    while src.curr() == Some(&Token::FOR) {
+        clauses.push(match_compre_clause(src)?);
+    }
+    let span = src.span_from(&expr.span);
+    Ok(AstNode::COMPRE { expr, clauses }.at(span))
 }
 
-fn parse_jx_expr(src: &TokenSrc) {
-    let token = src.curr();
-    match token {
-        Token::STRCONST(val) => (),
-        Token::INTCONST(val) => (),
-        _ => panic!(),
+fn match_compre_clause(src: &mut TokenSrc) -> Result<CompreClause, ParserErr> {
+    let start = src.expect(TokenKind::FOR, "to start list comprehension")?;
+    let var = match src.curr() {
+        Some(Token::ID(name)) => name.clone(),
+        Some(token) => {
+            return Err(ParserErr::InvalidComprehension {
+                reason: format!(
+                    "expected loop variable after `for`, found {}",
+                    describe_token(token)
+                ),
+                span: src.curr_span(),
+            })
+        }
+        None => return Err(src.unexpected(&[TokenKind::ID], "after `for`")),
     };
+    src.consume();
+    if !match_terminal(src, TokenKind::IN) {
+        if src.curr().is_none() {
+            return Err(src.unexpected(&[TokenKind::IN], "after loop variable"));
+        }
+        return Err(ParserErr::InvalidComprehension {
+            reason: format!("expected `in` after `for {}`", var),
+            span: src.span_from(&start),
+        });
+    }
+    // iterable_expr
+    let iter_expr = match_binary_expr(src, 0)?;
+    let mut conds = vec![];
+    while match_terminal(src, TokenKind::IF) {
+        conds.push(match_binary_expr(src, 0)?);
+    }
+    Ok(CompreClause {
+        var,
+        iter_expr,
+        conds,
+    })
 }
 
 /// match terminal/token, consume the token if matched
-fn match_terminal(src: &mut TokenSrc, terminal: Token) -> bool {
-    if src.curr() == &terminal {
+fn match_terminal(src: &mut TokenSrc, terminal: TokenKind) -> bool {
+    if src.curr().map(Token::kind) == Some(terminal) {
         src.consume();
         return true;
     }
     false
 }
 
-fn match_STRCONST(src: &mut TokenSrc) -> bool {
+fn match_id(src: &mut TokenSrc) -> Result<Ast, ParserErr> {
     match src.curr() {
-        Token::STRCONST(val) => {
+        Some(Token::ID(name)) => {
+            let id = AstNode::VAR(name.clone()).at(src.curr_span());
             src.consume();
-            return true;
+            Ok(id)
         }
-        _ => return false,
-    };
-}
-
-fn match_STRVAL(src: &mut TokenSrc) -> Option<String> {
-    let val = match src.curr() {
-        Token::STRCONST(val) => {
-            let new_val = val.clone();
-            src.consume();
-            return Some(new_val);
-        }
-        _ => return None,
-    };
-}
-
-fn match_int(src: &mut TokenSrc) -> Option<i32> {
-    let val = match src.curr() {
-        Token::INTCONST(val) => {
-            let new_val = val.clone();
-            src.consume();
-            return Some(new_val);
-        }
-        _ => return None,
-    };
-}
-
-fn match_double(src: &mut TokenSrc) -> Option<f64> {
-    let val = match src.curr() {
-        Token::DOUBLECONST(val) => {
-            let new_val = val.clone();
-            src.consume();
-            return Some(new_val);
-        }
-        _ => return None,
-    };
-}
-
-fn match_id(src: &mut TokenSrc) -> Option<Ast> {
-    match src.curr() {
-        Token::ID(name) => {
-            let id = name.clone();
-            src.consume();
-            Some(Box::new(AstNode::VAR(id)))
-        }
-        _ => None,
+        _ => Err(src.unexpected(&[TokenKind::ID], "")),
     }
 }
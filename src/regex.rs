@@ -0,0 +1,308 @@
+// Minimal matcher for POSIX extended regular expressions, used by `like()`.
+// Supports `.`, `[...]`, `[^...]`, `*`, `+`, `?`, `{m,n}`, `^`, `$`, `|`, `(...)` and `\` escapes.
+// The pattern is compiled to a Thompson NFA that is simulated over all states at once, so
+// matching is linear in the input, e.g. `(a*)*b` does not backtrack.
+
+enum Re {
+    Char(char),
+    Any,
+    // ranges of chars, negated
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    Concat(Vec<Re>),
+    Alt(Vec<Re>),
+    Repeat(Box<Re>, usize, Option<usize>),
+}
+
+// instruction of the compiled NFA
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    // continue at both targets
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+pub struct Regex {
+    prog: Vec<Inst>,
+}
+
+// states of the NFA at one input position, on avoids adding a state twice
+struct Threads {
+    pcs: Vec<usize>,
+    on: Vec<bool>,
+    matched: bool,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            pcs: vec![],
+            on: vec![false; len],
+            matched: false,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pcs.clear();
+        self.on.iter_mut().for_each(|on| *on = false);
+        self.matched = false;
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let re = parse_alt(&chars, &mut pos)?;
+        if pos < chars.len() {
+            return Err(format!("unmatched ) in regex {}", pattern));
+        }
+        let mut prog = vec![];
+        compile(&re, &mut prog)?;
+        prog.push(Inst::Match);
+        Ok(Regex { prog })
+    }
+
+    /// true if the regex matches any part of the input
+    pub fn is_match(&self, input: &str) -> bool {
+        let chars: Vec<char> = input.chars().collect();
+        let mut current = Threads::new(self.prog.len());
+        let mut next = Threads::new(self.prog.len());
+        for i in 0..=chars.len() {
+            // a match can start at any position
+            self.add_thread(&mut current, 0, &chars, i);
+            if current.matched {
+                return true;
+            }
+            if i == chars.len() {
+                break;
+            }
+            for &pc in &current.pcs {
+                let step = match &self.prog[pc] {
+                    Inst::Char(c) => chars[i] == *c,
+                    Inst::Any => true,
+                    Inst::Class(ranges, negated) => {
+                        ranges
+                            .iter()
+                            .any(|(lo, hi)| *lo <= chars[i] && chars[i] <= *hi)
+                            != *negated
+                    }
+                    _ => false,
+                };
+                if step {
+                    self.add_thread(&mut next, pc + 1, &chars, i + 1);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        false
+    }
+
+    // add the state at pc, following jumps and zero-width assertions at position i
+    fn add_thread(&self, threads: &mut Threads, pc: usize, s: &[char], i: usize) {
+        if threads.on[pc] {
+            return;
+        }
+        threads.on[pc] = true;
+        match &self.prog[pc] {
+            Inst::Split(a, b) => {
+                self.add_thread(threads, *a, s, i);
+                self.add_thread(threads, *b, s, i);
+            }
+            Inst::Jmp(target) => self.add_thread(threads, *target, s, i),
+            Inst::Start if i == 0 => self.add_thread(threads, pc + 1, s, i),
+            Inst::End if i == s.len() => self.add_thread(threads, pc + 1, s, i),
+            Inst::Start | Inst::End => (),
+            Inst::Match => threads.matched = true,
+            _ => threads.pcs.push(pc),
+        }
+    }
+}
+
+fn parse_alt(p: &[char], pos: &mut usize) -> Result<Re, String> {
+    let mut alts = vec![parse_concat(p, pos)?];
+    while *pos < p.len() && p[*pos] == '|' {
+        *pos += 1;
+        alts.push(parse_concat(p, pos)?);
+    }
+    if alts.len() == 1 {
+        return Ok(alts.pop().unwrap());
+    }
+    Ok(Re::Alt(alts))
+}
+
+fn parse_concat(p: &[char], pos: &mut usize) -> Result<Re, String> {
+    let mut items = vec![];
+    while *pos < p.len() && p[*pos] != '|' && p[*pos] != ')' {
+        let mut atom = parse_atom(p, pos)?;
+        while *pos < p.len() {
+            let (min, max) = match p[*pos] {
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                '{' => parse_bound(p, pos)?,
+                _ => break,
+            };
+            *pos += 1;
+            atom = Re::Repeat(Box::new(atom), min, max);
+        }
+        items.push(atom);
+    }
+    Ok(Re::Concat(items))
+}
+
+// bounds are expanded into copies of the repeated item, limited as RE_DUP_MAX in POSIX
+const DUP_MAX: usize = 255;
+
+// {m}, {m,} or {m,n}, leaves pos at the closing brace
+fn parse_bound(p: &[char], pos: &mut usize) -> Result<(usize, Option<usize>), String> {
+    let end = match p[*pos..].iter().position(|c| *c == '}') {
+        Some(offset) => *pos + offset,
+        None => return Err(String::from("unterminated { in regex")),
+    };
+    let bound: String = p[*pos + 1..end].iter().collect();
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid bound {{{}}} in regex", bound))
+    };
+    let (min, max) = match bound.split_once(',') {
+        Some((min, max)) if max.trim().is_empty() => (parse(min)?, None),
+        Some((min, max)) => (parse(min)?, Some(parse(max)?)),
+        None => (parse(&bound)?, Some(parse(&bound)?)),
+    };
+    if min > DUP_MAX || max.is_some_and(|max| max < min || max > DUP_MAX) {
+        return Err(format!("invalid bound {{{}}} in regex", bound));
+    }
+    *pos = end;
+    Ok((min, max))
+}
+
+fn parse_atom(p: &[char], pos: &mut usize) -> Result<Re, String> {
+    let c = p[*pos];
+    *pos += 1;
+    match c {
+        '(' => {
+            let re = parse_alt(p, pos)?;
+            if *pos >= p.len() || p[*pos] != ')' {
+                return Err(String::from("unmatched ( in regex"));
+            }
+            *pos += 1;
+            Ok(re)
+        }
+        '[' => parse_class(p, pos),
+        '.' => Ok(Re::Any),
+        '^' => Ok(Re::Start),
+        '$' => Ok(Re::End),
+        '*' | '+' | '?' => Err(format!("nothing to repeat before {} in regex", c)),
+        '\\' => {
+            if *pos >= p.len() {
+                return Err(String::from("trailing \\ in regex"));
+            }
+            *pos += 1;
+            Ok(Re::Char(p[*pos - 1]))
+        }
+        c => Ok(Re::Char(c)),
+    }
+}
+
+fn parse_class(p: &[char], pos: &mut usize) -> Result<Re, String> {
+    let mut negated = false;
+    if *pos < p.len() && p[*pos] == '^' {
+        negated = true;
+        *pos += 1;
+    }
+    let mut ranges = vec![];
+    // ] right after [ or [^ is a literal
+    let mut first = true;
+    loop {
+        if *pos >= p.len() {
+            return Err(String::from("unterminated [ in regex"));
+        }
+        let c = p[*pos];
+        *pos += 1;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+        if *pos + 1 < p.len() && p[*pos] == '-' && p[*pos + 1] != ']' {
+            ranges.push((c, p[*pos + 1]));
+            *pos += 2;
+        } else {
+            ranges.push((c, c));
+        }
+    }
+    Ok(Re::Class(ranges, negated))
+}
+
+// nested bounds multiply the size, e.g. ((a{255}){255}){255}
+const MAX_PROG_LEN: usize = 10_000;
+
+fn compile(re: &Re, prog: &mut Vec<Inst>) -> Result<(), String> {
+    if prog.len() > MAX_PROG_LEN {
+        return Err(String::from("regex is too large"));
+    }
+    match re {
+        Re::Char(c) => prog.push(Inst::Char(*c)),
+        Re::Any => prog.push(Inst::Any),
+        Re::Class(ranges, negated) => prog.push(Inst::Class(ranges.clone(), *negated)),
+        Re::Start => prog.push(Inst::Start),
+        Re::End => prog.push(Inst::End),
+        Re::Concat(items) => {
+            for item in items {
+                compile(item, prog)?;
+            }
+        }
+        Re::Alt(alts) => {
+            // every alternative but the last is split off and jumps past the others
+            let mut jumps = vec![];
+            for alt in &alts[..alts.len() - 1] {
+                let split = prog.len();
+                prog.push(Inst::Split(split + 1, 0));
+                compile(alt, prog)?;
+                jumps.push(prog.len());
+                prog.push(Inst::Jmp(0));
+                prog[split] = Inst::Split(split + 1, prog.len());
+            }
+            compile(&alts[alts.len() - 1], prog)?;
+            for jump in jumps {
+                prog[jump] = Inst::Jmp(prog.len());
+            }
+        }
+        Re::Repeat(inner, min, max) => {
+            for _ in 0..*min {
+                compile(inner, prog)?;
+            }
+            match max {
+                // loop back to the split after each repetition
+                None => {
+                    let split = prog.len();
+                    prog.push(Inst::Split(split + 1, 0));
+                    compile(inner, prog)?;
+                    prog.push(Inst::Jmp(split));
+                    prog[split] = Inst::Split(split + 1, prog.len());
+                }
+                // each optional repetition can skip to the end
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(prog.len());
+                        prog.push(Inst::Split(0, 0));
+                        compile(inner, prog)?;
+                    }
+                    for split in splits {
+                        prog[split] = Inst::Split(split + 1, prog.len());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,15 +1,17 @@
-use super::jx_token::Token;
+use super::jx_token::{SpannedToken, Token, TokenKind};
+use super::span::{Source, Span, Spanned};
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::io;
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum ScannerErr {
     // IO error when read input
     IO(io::Error),
     // Errors while scanning
-    Scan(String),
+    Scan { reason: String, span: Span },
 }
 
 impl Error for ScannerErr {}
@@ -18,600 +20,811 @@ impl fmt::Display for ScannerErr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
             ScannerErr::IO(err) => write!(f, "{}", err),
-            ScannerErr::Scan(reason) => write!(f, "{}", reason),
+            ScannerErr::Scan { reason, span } => write!(f, "{} at {}", reason, span),
         }
     }
 }
 
-pub fn scan_file(filename: &String) -> Result<Vec<Token>, ScannerErr> {
+impl ScannerErr {
+    /// error message followed by the source snippet where the error is
+    pub fn render(&self) -> String {
+        match &self {
+            ScannerErr::IO(_) => format!("{}\n", self),
+            ScannerErr::Scan { span, .. } => format!("{}\n{}", self, span.render()),
+        }
+    }
+}
+
+/// what the scanner accepts and produces besides standard JX
+#[derive(Clone, Copy, Default)]
+pub struct ScanOptions {
+    // accept `//` line comments and `/* */` block comments besides `#` comments
+    pub c_comments: bool,
+    // produce comments as COMMENT tokens instead of skipping them
+    pub keep_comments: bool,
+    // accept `.` for attribute access, e.g. config.site.scratch
+    pub dot_access: bool,
+}
+
+pub fn scan_file(filename: &String, options: ScanOptions) -> Result<Vec<SpannedToken>, ScannerErr> {
     let contents = match fs::read_to_string(filename) {
         Ok(contents) => contents,
         Err(e) => return Err(ScannerErr::IO(e)),
     };
-    scan_token(contents)
+    scan_with(contents, filename, options)
 }
 
-pub fn scan_token(raw: String) -> Result<Vec<Token>, ScannerErr> {
-    // reverse the input, so that the input is a stack/Vec whose top is the start of the input
-    let mut input: String = raw.chars().rev().collect();
-    let mut matching = String::from("");
-    let mut tokens: Vec<Token> = vec![];
-
-    if !move_forward(&mut input, &mut matching) {
-        return Err(ScannerErr::Scan(format!(
-            "can not read characters from input {}",
-            input
-        )));
-    }
-
-    let mut back_track = false;
-    loop {
-        match token_match(&matching) {
-            MatchResult::Matched(token) => {
-                //println!("token matched: {}", token.to_str());
-                tokens.push(token);
-                matching.clear();
-            }
-            MatchResult::More(opt) => {
-                if back_track {
-                    back_track = false; // only back track 1 char
-                    match opt {
-                        Some(token) => {
-                            println!("token matched: {}", token.to_str());
-                            tokens.push(token);
-                            matching.clear();
-                        }
-                        None => {
-                            return Err(ScannerErr::Scan(format!(
-                                "unable to match \"{}\"",
-                                matching
-                            )))
-                        }
-                    }
-                }
-            }
-            MatchResult::No => {
-                if back_track {
-                    // if already back tracking, then no match
-                    return Err(ScannerErr::Scan(format!(
-                        "unable to match \"{}\"",
-                        matching
-                    )));
-                } else {
-                    // start back tracking
-                    if !move_backward(&mut input, &mut matching) {
-                        panic!("matching string should not be empty");
-                    }
-                    back_track = true;
-                    continue;
-                }
-            }
-        };
-        if !move_forward(&mut input, &mut matching) {
-            break;
-        }
+/// scan input that is not from a file, e.g. expression supplied on the command line
+pub fn scan_token(raw: String) -> Result<Vec<SpannedToken>, ScannerErr> {
+    scan_source(raw, "<input>")
+}
+
+/// scan input, name is used in the location of tokens
+pub fn scan_source(raw: String, name: &str) -> Result<Vec<SpannedToken>, ScannerErr> {
+    scan_with(raw, name, ScanOptions::default())
+}
+
+pub fn scan_with(
+    raw: String,
+    name: &str,
+    options: ScanOptions,
+) -> Result<Vec<SpannedToken>, ScannerErr> {
+    let mut scanner = Scanner::with_options(raw, name, options);
+    let tokens = scanner
+        .by_ref()
+        .collect::<Result<Vec<SpannedToken>, ScannerErr>>()?;
+    if tokens
+        .iter()
+        .all(|token| token.kind() == TokenKind::COMMENT)
+    {
+        let end = scanner.source.text.len();
+        return Err(ScannerErr::Scan {
+            reason: String::from("no token in input"),
+            span: Span::new(&scanner.source, end, end),
+        });
     }
     Ok(tokens)
 }
 
-// move forward 1 char
-fn move_forward(src: &mut String, matching: &mut String) -> bool {
-    let mut c = match src.pop() {
-        Some(c) => c,
-        None => return false,
-    };
+/// lexer that reads the input once from start to end, tokens are produced lazily by iterating.
+/// iteration stops after the first error
+pub struct Scanner {
+    source: Rc<Source>,
+    // byte offset of the next char
+    pos: usize,
+    // last token produced, tells a negative number from a minus
+    prev: Option<Token>,
+    failed: bool,
+    options: ScanOptions,
+    // byte offset, line and column of the last span start, the next one is counted from here
+    cursor: (usize, usize, usize),
+}
 
-    match matching.chars().nth(0) {
-        Some(first) => {
-            if first == '\'' || first == '"' {
-                matching.push(c);
-                return true;
-            }
-        }
-        None => {
-            // skip whitespaces char in the begining of the matching string
-            while c.is_whitespace() {
-                c = match src.pop() {
-                    Some(c) => c,
-                    None => return false,
-                };
-            }
+impl Scanner {
+    pub fn new(raw: String, name: &str) -> Scanner {
+        Scanner::with_options(raw, name, ScanOptions::default())
+    }
+
+    pub fn with_options(raw: String, name: &str, options: ScanOptions) -> Scanner {
+        Scanner {
+            source: Source::new(name, raw),
+            pos: 0,
+            prev: None,
+            failed: false,
+            options,
+            cursor: (0, 1, 1),
         }
-    };
+    }
 
-    matching.push(c);
-    true
-}
+    /// next char, without consuming it
+    pub fn peek(&self) -> Option<char> {
+        self.source.text[self.pos..].chars().next()
+    }
 
-// move backward 1 char
-fn move_backward(src: &mut String, matching: &mut String) -> bool {
-    let c = match matching.pop() {
-        Some(c) => c,
-        None => return false,
-    };
-    src.push(c);
-    true
-}
+    /// the char after the next one
+    fn peek_second(&self) -> Option<char> {
+        self.source.text[self.pos..].chars().nth(1)
+    }
 
-enum MatchResult {
-    // matched, no need to read further
-    Matched(Token),
-    // need to read further
-    More(Option<Token>),
-    // not matched, no need to read further
-    No,
-}
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
 
-fn token_match(input: &str) -> MatchResult {
-    let c = match input.chars().nth(0) {
-        Some(c) => c,
-        None => return MatchResult::No,
-    };
-    //println!("t_m(), input {}", input);
-    match c {
-        '{' => match_1_char_token(input, Token::LBRAC),
-        '}' => match_1_char_token(input, Token::RBRAC),
-        '[' => match_1_char_token(input, Token::LSQBRAC),
-        ']' => match_1_char_token(input, Token::RSQBRAC),
-        '(' => match_1_char_token(input, Token::LPAREN),
-        ')' => match_1_char_token(input, Token::RPAREN),
-        ':' => match_1_char_token(input, Token::COLON),
-        ',' => match_1_char_token(input, Token::COMMA),
-        '+' => match_1_char_token(input, Token::ADD),
-        '-' => match_negative_or_minus(input),
-        '*' => match_1_char_token(input, Token::MUL),
-        '/' => match_1_char_token(input, Token::DIV),
-        '%' => match_1_char_token(input, Token::MOD),
-        '=' => {
-            if input.eq("==") {
-                MatchResult::Matched(Token::EQ)
-            } else {
-                MatchResult::More(None)
-            }
+    /// consume the next char if it is c
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            return true;
         }
-        '!' => {
-            if input.eq("!=") {
-                MatchResult::Matched(Token::NE)
-            } else {
-                MatchResult::More(None)
+        false
+    }
+
+    fn bump_while(&mut self, pred: fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
             }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// span from start to the current position, start should not go backward
+    fn span_from(&mut self, start: usize) -> Span {
+        // counting from the last span keeps scanning linear on long lines
+        let (offset, line, col) = self.cursor;
+        let skipped = &self.source.text[offset..start];
+        let (line, col) = match skipped.rfind('\n') {
+            Some(i) => (
+                line + skipped.matches('\n').count(),
+                skipped[i + 1..].chars().count() + 1,
+            ),
+            None => (line, col + skipped.chars().count()),
+        };
+        self.cursor = (start, line, col);
+        Span {
+            source: Some(Rc::clone(&self.source)),
+            start,
+            end: self.pos,
+            line,
+            col,
+        }
+    }
+
+    fn error(&mut self, reason: String, start: usize) -> ScannerErr {
+        ScannerErr::Scan {
+            reason,
+            span: self.span_from(start),
         }
-        '<' => {
-            if input.eq("<=") {
-                MatchResult::Matched(Token::LE)
-            } else if input.len() == 1 {
-                MatchResult::More(Some(Token::LT))
-            } else {
-                MatchResult::No
+    }
+
+    fn scan(&mut self, start: usize) -> Result<Token, ScannerErr> {
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(self.error(String::from("unexpected end of input"), start)),
+        };
+        let token = match c {
+            '{' => Token::LBRAC,
+            '}' => Token::RBRAC,
+            '[' => Token::LSQBRAC,
+            ']' => Token::RSQBRAC,
+            '(' => Token::LPAREN,
+            ')' => Token::RPAREN,
+            ':' => Token::COLON,
+            ',' => Token::COMMA,
+            '.' if self.options.dot_access => Token::DOT,
+            '+' => Token::ADD,
+            '*' => Token::MUL,
+            '#' => self.scan_line_comment(start),
+            '/' if self.options.c_comments && self.peek() == Some('/') => {
+                self.scan_line_comment(start)
+            }
+            '/' if self.options.c_comments && self.peek() == Some('*') => {
+                self.scan_block_comment(start)?
+            }
+            '/' => Token::DIV,
+            '%' => Token::MOD,
+            // number right after an operand is subtracted, e.g. "x-1"
+            '-' if !self.after_operand() && self.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                self.scan_number(start)?
             }
+            '-' => Token::MINUS,
+            '=' if self.eat('=') => Token::EQ,
+            '!' if self.eat('=') => Token::NE,
+            '<' if self.eat('=') => Token::LE,
+            '<' => Token::LT,
+            '>' if self.eat('=') => Token::GE,
+            '>' => Token::GT,
+            '0'..='9' => self.scan_number(start)?,
+            'a'..='z' | 'A'..='Z' => self.scan_word(start),
+            '\'' | '"' => self.scan_string(start, c)?,
+            c => return Err(self.error(format!("unexpected character '{}'", c), start)),
+        };
+        Ok(token)
+    }
+
+    // previous token ends an operand
+    fn after_operand(&self) -> bool {
+        matches!(
+            self.prev,
+            Some(Token::INTCONST(_))
+                | Some(Token::DOUBLECONST(_))
+                | Some(Token::STRCONST(_))
+                | Some(Token::BOOLCONST(_))
+                | Some(Token::NULL)
+                | Some(Token::ID(_))
+                | Some(Token::RPAREN)
+                | Some(Token::RSQBRAC)
+                | Some(Token::RBRAC)
+        )
+    }
+
+    // JSON number: integer part without leading zeros, optional fraction and exponent.
+    // start may point to a leading '-'
+    fn scan_number(&mut self, start: usize) -> Result<Token, ScannerErr> {
+        let int_start = if self.source.text[start..].starts_with('-') {
+            start + 1
+        } else {
+            start
+        };
+        self.bump_while(|c| c.is_ascii_digit());
+        let mut is_double = false;
+        if self.peek() == Some('.') && self.peek_second().is_some_and(|c| c.is_ascii_digit()) {
+            is_double = true;
+            self.bump();
+            self.bump_while(|c| c.is_ascii_digit());
         }
-        '>' => {
-            if input.starts_with(">=") {
-                MatchResult::Matched(Token::GE)
-            } else if input.len() == 1 {
-                MatchResult::More(Some(Token::GT))
-            } else {
-                MatchResult::No
+        if matches!(self.peek(), Some('e') | Some('E')) {
+            let exp = &self.source.text[self.pos + 1..];
+            let sign_len = if exp.starts_with(['+', '-']) { 1 } else { 0 };
+            if exp[sign_len..].starts_with(|c: char| c.is_ascii_digit()) {
+                is_double = true;
+                self.pos += 1 + sign_len;
+                self.bump_while(|c| c.is_ascii_digit());
             }
         }
-        '0'..='9' => match_numeric(input),
-        // ID
-        'a'..='z' => match_alphabetic(input),
-        'A'..='Z' => match_id(input),
+        // e.g. "12abc", "2.", "1e", "0x1f" or "1_000"
+        if self
+            .peek()
+            .is_some_and(|c| c == '.' || c == '_' || c.is_alphanumeric())
+        {
+            self.bump_while(|c| c == '.' || c == '_' || c.is_alphanumeric());
+            let text = &self.source.text[start..self.pos];
+            return Err(self.error(format!("invalid number \"{}\"", text), start));
+        }
+        let text = &self.source.text[start..self.pos];
+        let int_part = self.source.text[int_start..].split(|c: char| !c.is_ascii_digit());
+        if int_part
+            .into_iter()
+            .next()
+            .is_some_and(|digits| digits.len() > 1 && digits.starts_with('0'))
+        {
+            return Err(self.error(format!("invalid number \"{}\", leading zero", text), start));
+        }
+        if !is_double {
+            return match text.parse::<i64>() {
+                Ok(val) => Ok(Token::INTCONST(val)),
+                Err(_) => Err(self.error(format!("integer {} is out of range", text), start)),
+            };
+        }
+        match text.parse::<f64>() {
+            Ok(val) if val.is_finite() => Ok(Token::DOUBLECONST(val)),
+            _ => Err(self.error(format!("number {} is out of range", text), start)),
+        }
+    }
 
-        // String literal
-        '\'' => match_strconst(input, '\''),
-        '"' => match_strconst(input, '"'),
-        _ => MatchResult::No,
+    // keyword or ID
+    fn scan_word(&mut self, start: usize) -> Token {
+        self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        match &self.source.text[start..self.pos] {
+            "false" => Token::BOOLCONST(false),
+            "true" => Token::BOOLCONST(true),
+            "for" => Token::FOR,
+            "null" => Token::NULL,
+            "not" => Token::NOT,
+            "in" => Token::IN,
+            "if" => Token::IF,
+            "else" => Token::ELSE,
+            "and" => Token::AND,
+            "or" => Token::OR,
+            id => Token::ID(String::from(id)),
+        }
     }
-}
 
-fn match_numeric(input: &str) -> MatchResult {
-    match input.parse::<i32>().ok() {
-        Some(val) => return MatchResult::More(Some(Token::INTCONST(val))),
-        None => (),
-    };
-    // not Matched ending with dot, e.g. "123."
-    match input.chars().last() {
-        Some(c) => {
-            if c == '.' {
-                match &input[..input.len() - 1].parse::<i32>().ok() {
-                    Some(_) => return MatchResult::More(None),
-                    None => return MatchResult::No,
-                };
+    // comment up to the end of the line, the line break is not part of it
+    fn scan_line_comment(&mut self, start: usize) -> Token {
+        self.bump_while(|c| c != '\n');
+        let text = self.source.text[start..self.pos].trim_end_matches('\r');
+        Token::COMMENT(String::from(text))
+    }
+
+    // /* ... */, not nested, the opening slash is consumed
+    fn scan_block_comment(&mut self, start: usize) -> Result<Token, ScannerErr> {
+        self.bump();
+        match self.source.text[self.pos..].find("*/") {
+            Some(len) => {
+                self.pos += len + 2;
+                Ok(Token::COMMENT(String::from(
+                    &self.source.text[start..self.pos],
+                )))
+            }
+            None => {
+                // underline the opening /*
+                self.pos = start + 2;
+                Err(self.error(String::from("unterminated comment"), start))
             }
         }
-        None => (),
-    };
-    match input.parse::<f64>().ok() {
-        Some(val) => return MatchResult::More(Some(Token::DOUBLECONST(val))),
-        None => (),
     }
 
-    MatchResult::No
-}
-
-// assume input[0] == '-'
-fn match_negative_or_minus(input: &str) -> MatchResult {
-    if input.len() == 1 {
-        return MatchResult::Matched(Token::MINUS);
+    // string literal with escapes decoded, the opening quote is consumed
+    fn scan_string(&mut self, start: usize, quote: char) -> Result<Token, ScannerErr> {
+        let mut val = String::new();
+        loop {
+            // copy everything up to the next quote or backslash at once
+            let rest = &self.source.text[self.pos..];
+            let len = match rest.find([quote, '\\']) {
+                Some(len) => len,
+                None => return Err(self.unterminated_string(start, quote)),
+            };
+            val.push_str(&rest[..len]);
+            self.pos += len;
+            if self.bump() == Some(quote) {
+                return Ok(Token::STRCONST(val));
+            }
+            val.push(self.scan_escape(start, quote)?);
+        }
     }
-    let opt = match match_numeric(&input[1..]) {
-        MatchResult::More(opt) => opt,
-        _ => return MatchResult::No,
-    };
-    let token = match opt {
-        Some(token) => token,
-        None => return MatchResult::No,
-    };
-    match token {
-        Token::INTCONST(val) => MatchResult::More(Some(Token::INTCONST(-val))),
-        Token::DOUBLECONST(val) => MatchResult::More(Some(Token::DOUBLECONST(-val))),
-        _ => MatchResult::No,
+
+    // underline the opening quote
+    fn unterminated_string(&mut self, start: usize, quote: char) -> ScannerErr {
+        self.pos = start + quote.len_utf8();
+        self.error(String::from("unterminated string"), start)
     }
-}
 
-// match token that only consist of 1 character
-fn match_1_char_token(input: &str, token: Token) -> MatchResult {
-    if input.len() > 1 {
-        return MatchResult::No;
+    // char after a backslash, same escapes as JSON plus \'
+    fn scan_escape(&mut self, str_start: usize, quote: char) -> Result<char, ScannerErr> {
+        let start = self.pos - 1;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(self.unterminated_string(str_start, quote)),
+        };
+        let escaped = match c {
+            '"' => '"',
+            '\'' => '\'',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{08}',
+            'f' => '\u{0c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => self.scan_unicode_escape(start)?,
+            c => return Err(self.error(format!("invalid escape sequence '\\{}'", c), start)),
+        };
+        Ok(escaped)
     }
-    MatchResult::Matched(token)
-}
 
-// match anything start with a alphabet
-fn match_alphabetic(input: &str) -> MatchResult {
-    let c = match input.chars().nth(0) {
-        Some(c) => c,
-        None => return MatchResult::No,
-    };
-    match c {
-        'f' => {
-            if input == "false" {
-                return MatchResult::More(Some(Token::BOOLCONST(false)));
-            } else if input == "for" {
-                return MatchResult::More(Some(Token::FOR));
+    // \uXXXX, chars outside the BMP are written as a UTF-16 surrogate pair
+    fn scan_unicode_escape(&mut self, start: usize) -> Result<char, ScannerErr> {
+        let mut code = self.scan_hex4(start)?;
+        if (0xD800..0xDC00).contains(&code) {
+            if !(self.eat('\\') && self.eat('u')) {
+                return Err(self.error(String::from("unpaired surrogate in unicode escape"), start));
             }
-        }
-        't' => {
-            if input == "true" {
-                return MatchResult::More(Some(Token::BOOLCONST(true)));
+            let low = self.scan_hex4(start)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error(
+                    String::from("invalid surrogate pair in unicode escape"),
+                    start,
+                ));
             }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
         }
-        'n' => {
-            if input == "null" {
-                return MatchResult::More(Some(Token::NULL));
-            }
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => Err(self.error(String::from("unpaired surrogate in unicode escape"), start)),
         }
-        'i' => {
-            if input == "in" {
-                return MatchResult::More(Some(Token::IN));
+    }
+
+    fn scan_hex4(&mut self, start: usize) -> Result<u32, ScannerErr> {
+        let digits_start = self.pos;
+        for _ in 0..4 {
+            match self.peek() {
+                Some(c) if c.is_ascii_hexdigit() => self.pos += 1,
+                _ => {
+                    let reason = String::from("invalid unicode escape, expected 4 hex digits");
+                    return Err(self.error(reason, start));
+                }
             }
         }
-        _ => (),
-    };
-    match_id(input)
+        let digits = &self.source.text[digits_start..self.pos];
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
 }
 
-// assume the 1st char is a letter
-fn match_id(input: &str) -> MatchResult {
-    for c in input.chars() {
-        if c.is_whitespace() {
-            return MatchResult::No;
+impl Iterator for Scanner {
+    type Item = Result<SpannedToken, ScannerErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
         }
-        match c {
-            'a'..='z' => continue,
-            'A'..='Z' => continue,
-            '0'..='9' => continue,
-            '_' => continue,
-            _ => return MatchResult::No,
+        loop {
+            self.bump_while(char::is_whitespace);
+            let start = self.pos;
+            self.peek()?;
+            match self.scan(start) {
+                Ok(Token::COMMENT(_)) if !self.options.keep_comments => continue,
+                Ok(Token::COMMENT(text)) => {
+                    return Some(Ok(Spanned::new(
+                        Token::COMMENT(text),
+                        self.span_from(start),
+                    )))
+                }
+                Ok(token) => {
+                    self.prev = Some(token.clone());
+                    return Some(Ok(Spanned::new(token, self.span_from(start))));
+                }
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
         }
     }
-    MatchResult::More(Some(Token::ID(String::from(input))))
-}
-
-fn match_strconst(input: &str, quote: char) -> MatchResult {
-    if input.ends_with(quote) && input.len() > 1 {
-        let str_const = &input[1..input.len() - 1];
-        MatchResult::Matched(Token::STRCONST(String::from(str_const)))
-    } else {
-        MatchResult::More(None)
-    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::token_match;
-    use super::MatchResult;
+    use super::scan_with;
+    use super::ScanOptions;
+    use super::Scanner;
+    use super::ScannerErr;
     use super::Token;
 
+    // first token of the input
+    fn scan_first(input: &str) -> Result<Token, ScannerErr> {
+        match Scanner::new(String::from(input), "<test>").next() {
+            Some(result) => result.map(|token| token.node),
+            None => panic!("Should have token"),
+        }
+    }
+
     #[test]
     fn match_intconst1() {
-        let input = String::from("9");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::INTCONST(val) => assert_eq!(val, 9),
+        match scan_first("9") {
+            Ok(Token::INTCONST(val)) => assert_eq!(val, 9),
             _ => panic!("Should match INTCONST"),
         }
     }
 
     #[test]
     fn match_intconst2() {
-        let input = String::from("12345abc");
-        match token_match(&input) {
-            MatchResult::No => return,
-            _ => panic!("Should not match"),
-        };
+        if scan_first("12345abc").is_ok() {
+            panic!("Should not match");
+        }
     }
 
     #[test]
     fn match_intconst3() {
-        let input = String::from("143241");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::INTCONST(val) => assert_eq!(val, 143241),
+        match scan_first("143241") {
+            Ok(Token::INTCONST(val)) => assert_eq!(val, 143241),
             _ => panic!("Should match INTCONST"),
         }
     }
 
     #[test]
     fn match_negative_intconst() {
-        let input = String::from("-534");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::INTCONST(val) => assert_eq!(val, -534),
+        match scan_first("-534") {
+            Ok(Token::INTCONST(val)) => assert_eq!(val, -534),
             _ => panic!("Should match INTCONST"),
         }
     }
 
+    #[test]
+    fn match_minus_after_operand() {
+        let tokens: Vec<Token> = Scanner::new(String::from("x-1"), "<test>")
+            .map(|token| token.unwrap().node)
+            .collect();
+        assert!(
+            tokens
+                == vec![
+                    Token::ID(String::from("x")),
+                    Token::MINUS,
+                    Token::INTCONST(1)
+                ]
+        );
+    }
+
     #[test]
     fn match_strconst_single_quotes() {
-        let input = String::from("'foo bar'");
-        let token = match token_match(&input) {
-            MatchResult::Matched(token) => token,
-            _ => panic!("Should Matched"),
-        };
-        match token {
-            Token::STRCONST(val) => assert_eq!(val, "foo bar"),
+        match scan_first("'foo bar'") {
+            Ok(Token::STRCONST(val)) => assert_eq!(val, "foo bar"),
             _ => panic!("Should match STRCONST"),
         }
     }
 
     #[test]
     fn match_strconst_double_quotes() {
-        let input = String::from("\"foo bar\"");
-        let token = match token_match(&input) {
-            MatchResult::Matched(token) => token,
-            _ => panic!("Should Matched"),
-        };
-        match token {
-            Token::STRCONST(val) => assert_eq!(val, "foo bar"),
+        match scan_first("\"foo bar\"") {
+            Ok(Token::STRCONST(val)) => assert_eq!(val, "foo bar"),
             _ => panic!("Should match STRCONST"),
         }
     }
 
     #[test]
     fn match_strconst_mixed_quotes1() {
-        let input = String::from("'foo_bar\"");
-        match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                None => return,
-                _ => panic!("Should matched MORE(None)"),
-            },
-            _ => panic!("Should matched More"),
-        };
+        match scan_first("'foo_bar\"") {
+            Err(err) => assert!(err.to_string().starts_with("unterminated string")),
+            Ok(_) => panic!("Should not match"),
+        }
     }
 
     #[test]
     fn match_strconst_mixed_quotes2() {
-        let input = String::from("\"foo_bar'");
-        match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                None => return,
-                _ => panic!("Should matched MORE(None)"),
-            },
-            _ => panic!("Should matched More"),
-        };
+        match scan_first("\"foo_bar'") {
+            Err(err) => assert!(err.to_string().starts_with("unterminated string")),
+            Ok(_) => panic!("Should not match"),
+        }
+    }
+
+    #[test]
+    fn match_strconst_escapes() {
+        let input = r#""a \"quoted\" \'word\'\\ \/\n\t\b\f\r \u00e9\ud83d\ude00""#;
+        match scan_first(input) {
+            Ok(Token::STRCONST(val)) => {
+                assert_eq!(
+                    "a \"quoted\" 'word'\\ /\n\t\u{08}\u{0c}\r \u{e9}\u{1f600}",
+                    val
+                )
+            }
+            _ => panic!("Should match STRCONST"),
+        }
+        match scan_first(r#"'it\'s'"#) {
+            Ok(Token::STRCONST(val)) => assert_eq!("it's", val),
+            _ => panic!("Should match STRCONST"),
+        }
+    }
+
+    #[test]
+    fn match_strconst_invalid_escapes() {
+        let inputs = vec![
+            (r#""a\qb""#, "invalid escape sequence '\\q'"),
+            (
+                r#""\u12g4""#,
+                "invalid unicode escape, expected 4 hex digits",
+            ),
+            (r#""\ud83d""#, "unpaired surrogate in unicode escape"),
+            (r#""\ude00""#, "unpaired surrogate in unicode escape"),
+            (r#""abc\""#, "unterminated string"),
+        ];
+        for (input, reason) in inputs {
+            match scan_first(input) {
+                Err(ScannerErr::Scan { reason: got, .. }) => assert_eq!(reason, got),
+                _ => panic!("{} should be error", input),
+            }
+        }
     }
 
     #[test]
     fn match_id1() {
-        let input = String::from("a");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::ID(val) => assert_eq!(val, "a"),
+        match scan_first("a") {
+            Ok(Token::ID(val)) => assert_eq!(val, "a"),
             _ => panic!("Should match ID"),
         }
     }
 
     #[test]
     fn match_id2() {
-        let input = String::from("foo_bar");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::ID(val) => assert_eq!(val, "foo_bar"),
+        match scan_first("foo_bar") {
+            Ok(Token::ID(val)) => assert_eq!(val, "foo_bar"),
             _ => panic!("Should match ID"),
         }
     }
 
     #[test]
     fn match_id3() {
-        let input = String::from("FOO_BAR");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should match MORE(token)"),
-            },
-            _ => panic!("Should match MORE"),
-        };
-        match token {
-            Token::ID(val) => assert_eq!(val, "FOO_BAR"),
+        match scan_first("FOO_BAR") {
+            Ok(Token::ID(val)) => assert_eq!(val, "FOO_BAR"),
             _ => panic!("Should match ID"),
         }
     }
 
     #[test]
     fn match_id_whitespace() {
-        let input = String::from("foo bar");
-        match token_match(&input) {
-            MatchResult::No => return,
-            _ => panic!("Should Not matched"),
-        };
+        // whitespace ends the ID
+        match scan_first("foo bar") {
+            Ok(Token::ID(val)) => assert_eq!(val, "foo"),
+            _ => panic!("Should match ID"),
+        }
     }
 
     #[test]
     fn match_bool_true() {
-        let input = String::from("true");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                None => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::BOOLCONST(true) => return,
-            Token::BOOLCONST(false) => panic!("Should match true"),
+        match scan_first("true") {
+            Ok(Token::BOOLCONST(true)) => (),
+            Ok(Token::BOOLCONST(false)) => panic!("Should match true"),
             _ => panic!("Should match BOOLCONST"),
         }
     }
 
     #[test]
     fn match_bool_false() {
-        let input = String::from("false");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                None => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::BOOLCONST(true) => panic!("should match false"),
-            Token::BOOLCONST(false) => return,
+        match scan_first("false") {
+            Ok(Token::BOOLCONST(true)) => panic!("should match false"),
+            Ok(Token::BOOLCONST(false)) => (),
             _ => panic!("Should match BOOLCONST"),
         }
     }
 
     #[test]
     fn match_double_zero() {
-        let input = String::from("0.0");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                None => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::DOUBLECONST(f) => assert_eq!(0.0, f),
+        match scan_first("0.0") {
+            Ok(Token::DOUBLECONST(f)) => assert_eq!(0.0, f),
             _ => panic!("Should match DOUBLECONST"),
         }
     }
 
     #[test]
     fn match_double1() {
-        let input = String::from("1.0");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                None => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::DOUBLECONST(f) => assert_eq!(1.0, f),
+        match scan_first("1.0") {
+            Ok(Token::DOUBLECONST(f)) => assert_eq!(1.0, f),
             _ => panic!("Should match DOUBLECONST"),
         }
     }
 
     #[test]
     fn match_double2() {
-        let input = String::from("2.14523124695");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                None => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::DOUBLECONST(f) => assert_eq!(2.14523124695, f),
+        match scan_first("2.14523124695") {
+            Ok(Token::DOUBLECONST(f)) => assert_eq!(2.14523124695, f),
             _ => panic!("Should match DOUBLECONST"),
         }
     }
 
+    #[test]
+    fn match_number_syntax() {
+        let doubles = vec![
+            ("1e9", 1e9),
+            ("2.5E-3", 2.5e-3),
+            ("-1.5e+2", -150.0),
+            ("0.5", 0.5),
+        ];
+        for (input, expected) in doubles {
+            match scan_first(input) {
+                Ok(Token::DOUBLECONST(f)) => assert_eq!(expected, f),
+                _ => panic!("{} should match DOUBLECONST", input),
+            }
+        }
+        match scan_first("9223372036854775807") {
+            Ok(Token::INTCONST(val)) => assert_eq!(i64::MAX, val),
+            _ => panic!("Should match INTCONST"),
+        }
+        let invalid = vec![
+            (
+                "9223372036854775808",
+                "integer 9223372036854775808 is out of range",
+            ),
+            ("1e999", "number 1e999 is out of range"),
+            ("012", "invalid number \"012\", leading zero"),
+            ("0x1f", "invalid number \"0x1f\""),
+            ("1_000", "invalid number \"1_000\""),
+            ("1e", "invalid number \"1e\""),
+        ];
+        for (input, reason) in invalid {
+            match scan_first(input) {
+                Err(ScannerErr::Scan { reason: got, .. }) => assert_eq!(reason, got),
+                _ => panic!("{} should be error", input),
+            }
+        }
+    }
+
     #[test]
     fn match_double_end_with_dot() {
-        let input = String::from("2.");
-        match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                None => return,
-                Some(_) => panic!("Should matched More(None)"),
-            },
-            _ => panic!("Should matched More"),
-        };
+        if scan_first("2.").is_ok() {
+            panic!("Should not match");
+        }
     }
 
     #[test]
     fn match_null() {
-        let input = String::from("null");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::NULL => return,
+        match scan_first("null") {
+            Ok(Token::NULL) => (),
             _ => panic!("Should matched NULL"),
         }
     }
 
     #[test]
     fn match_null_cap() {
-        let input = String::from("NULL");
-        let token = match token_match(&input) {
-            MatchResult::More(opt) => match opt {
-                Some(token) => token,
-                _ => panic!("Should matched More(token)"),
-            },
-            _ => panic!("Should matched More"),
-        };
-        match token {
-            Token::ID(name) => assert_eq!("NULL", name),
+        match scan_first("NULL") {
+            Ok(Token::ID(name)) => assert_eq!("NULL", name),
             _ => panic!("Should matched ID"),
         }
     }
+
+    #[test]
+    fn match_keywords() {
+        let keywords = vec![
+            ("not", Token::NOT),
+            ("and", Token::AND),
+            ("or", Token::OR),
+            ("if", Token::IF),
+            ("else", Token::ELSE),
+        ];
+        for (input, expected) in keywords {
+            match scan_first(input) {
+                Ok(token) => assert!(token == expected),
+                Err(err) => panic!("Should match keyword: {}", err),
+            }
+        }
+        // prefix of keyword is ID
+        match scan_first("ord") {
+            Ok(Token::ID(name)) => assert_eq!("ord", name),
+            _ => panic!("Should matched ID"),
+        }
+    }
+
+    #[test]
+    fn match_comments() {
+        let scan = |input: &str, options: ScanOptions| -> Result<Vec<Token>, ScannerErr> {
+            Scanner::with_options(String::from(input), "<test>", options)
+                .map(|token| token.map(|token| token.node))
+                .collect()
+        };
+        let input = "# head\n1 // c\n/* a\nb */ 2";
+        // `//` is division without c comments
+        let tokens = scan("1 // c", ScanOptions::default()).unwrap();
+        assert!(tokens[1] == Token::DIV && tokens[2] == Token::DIV);
+
+        let c_comments = ScanOptions {
+            c_comments: true,
+            keep_comments: false,
+            dot_access: false,
+        };
+        let tokens = scan(input, c_comments).unwrap();
+        assert!(tokens == vec![Token::INTCONST(1), Token::INTCONST(2)]);
+
+        let keep_comments = ScanOptions {
+            c_comments: true,
+            keep_comments: true,
+            dot_access: false,
+        };
+        let tokens = scan(input, keep_comments).unwrap();
+        let expected = vec![
+            Token::COMMENT(String::from("# head")),
+            Token::INTCONST(1),
+            Token::COMMENT(String::from("// c")),
+            Token::COMMENT(String::from("/* a\nb */")),
+            Token::INTCONST(2),
+        ];
+        assert!(tokens == expected);
+
+        match scan("1 /* a", c_comments) {
+            Err(err) => assert_eq!("unterminated comment at <test>:1:3", err.to_string()),
+            Ok(_) => panic!("Should not match"),
+        }
+
+        // comments only, the error points at the end of input
+        match scan_with(
+            String::from("\n# only a comment\n"),
+            "<test>",
+            keep_comments,
+        ) {
+            Err(err) => assert_eq!("no token in input at <test>:3:1", err.to_string()),
+            Ok(_) => panic!("Should not match"),
+        }
+    }
+
+    #[test]
+    fn match_dot() {
+        let scan = |input: &str, options: ScanOptions| -> Result<Vec<Token>, ScannerErr> {
+            Scanner::with_options(String::from(input), "<test>", options)
+                .map(|token| token.map(|token| token.node))
+                .collect()
+        };
+        let dot_access = ScanOptions {
+            dot_access: true,
+            ..ScanOptions::default()
+        };
+        let tokens = scan("a.b 1.5", dot_access).unwrap();
+        let expected = vec![
+            Token::ID(String::from("a")),
+            Token::DOT,
+            Token::ID(String::from("b")),
+            Token::DOUBLECONST(1.5),
+        ];
+        assert!(tokens == expected);
+        match scan("a.b", ScanOptions::default()) {
+            Err(err) => assert_eq!("unexpected character '.' at <test>:1:2", err.to_string()),
+            Ok(_) => panic!("Should not match"),
+        }
+    }
+
+    #[test]
+    fn scanner_peek() {
+        let mut scanner = Scanner::new(String::from(" [1]"), "<test>");
+        assert_eq!(Some(' '), scanner.peek());
+        assert!(scanner.next().unwrap().unwrap().node == Token::LSQBRAC);
+        assert_eq!(Some('1'), scanner.peek());
+    }
 }
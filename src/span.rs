@@ -0,0 +1,182 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+/// input text and its name, shared by the spans that point into it
+pub struct Source {
+    pub name: String,
+    pub text: String,
+    // byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    pub fn new(name: &str, text: String) -> Rc<Source> {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Rc::new(Source {
+            name: String::from(name),
+            text,
+            line_starts,
+        })
+    }
+
+    /// 1-based line and column (in chars) of a byte offset
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let col = self.text[line_start..offset].chars().count() + 1;
+        (line, col)
+    }
+
+    /// text of a 1-based line, without the line break
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.text.len(),
+        };
+        self.text[start..end].trim_end_matches('\r')
+    }
+}
+
+/// location of a token or node, byte range [start, end) in the source.
+/// span without source is used for nodes that are not from any input
+#[derive(Clone, Default)]
+pub struct Span {
+    pub source: Option<Rc<Source>>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub fn new(source: &Rc<Source>, start: usize, end: usize) -> Span {
+        let (line, col) = source.position(start);
+        Span {
+            source: Some(Rc::clone(source)),
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    /// span from the start of self to the end of other
+    pub fn to(&self, other: &Span) -> Span {
+        if self.source.is_none() {
+            return other.clone();
+        }
+        Span {
+            end: other.end.max(self.end),
+            ..self.clone()
+        }
+    }
+
+    /// empty span at the end of self
+    pub fn end_point(&self) -> Span {
+        match &self.source {
+            Some(source) => Span::new(source, self.end, self.end),
+            None => Span::default(),
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        match &self.source {
+            Some(source) => &source.name,
+            None => "<unknown>",
+        }
+    }
+
+    /// the source line of the span with the span underlined, e.g.
+    ///
+    /// ```text
+    ///   --> rules.jx:2:15
+    ///    |
+    ///  2 |     "command" "echo"
+    ///    |               ^^^^^^
+    /// ```
+    pub fn render(&self) -> String {
+        let source = match &self.source {
+            Some(source) => source,
+            None => return String::new(),
+        };
+        let line_text = source.line_text(self.line);
+        let line_no = self.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        let indent: String = line_text
+            .chars()
+            .take(self.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // underline up to the end of the line
+        let width = line_text.chars().skip(self.col - 1).count();
+        let len = source.text[self.start..self.end].chars().count();
+        let carets = "^".repeat(len.min(width).max(1));
+        format!(
+            "{gutter}--> {}\n{gutter} |\n{} | {}\n{gutter} | {}{}\n",
+            self,
+            line_no,
+            line_text,
+            indent,
+            carets,
+            gutter = gutter
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}:{}:{}", source.name, self.line, self.col),
+            None => write!(f, "<unknown>"),
+        }
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// token or AST node with its location
+#[derive(Clone, Debug)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Spanned<T> {
+        Spanned { node, span }
+    }
+}
+
+// node without location
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Spanned<T> {
+        Spanned::new(node, Span::default())
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt(f)
+    }
+}
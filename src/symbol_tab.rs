@@ -1,3 +1,4 @@
+use super::ast::Ast;
 use std::collections::HashMap;
 use std::fmt;
 
@@ -5,6 +6,12 @@ pub struct SymbolTable {
     symbols: HashMap<String, Symbol>,
 }
 
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolTable {
     pub fn new() -> SymbolTable {
         SymbolTable {
@@ -26,42 +33,15 @@ impl SymbolTable {
 }
 
 pub enum Symbol {
-    LIST(Vec<Symbol>),
-    OBJECT(HashMap<String, Symbol>),
-    INT(i32),
-    DOUBLE(f64),
-    BOOL(bool),
+    // JX expression bound to the name, evaluated when the name is referenced
+    EXPR(Ast),
     FUNC { return_type: ValueType },
 }
 
 impl fmt::Display for Symbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            Symbol::LIST(list) => {
-                if let Err(err) = write!(f, "[ ") {
-                    return Err(err);
-                }
-                for symbol in list {
-                    if let Err(err) = write!(f, "{}", symbol) {
-                        return Err(err);
-                    }
-                }
-                write!(f, " ]")
-            }
-            Symbol::OBJECT(obj) => {
-                if let Err(err) = write!(f, "{{ ") {
-                    return Err(err);
-                }
-                for (name, symbol) in obj {
-                    if let Err(err) = write!(f, "{} : {},", name, symbol) {
-                        return Err(err);
-                    }
-                }
-                write!(f, " }}")
-            }
-            Symbol::INT(val) => write!(f, "{}", val),
-            Symbol::DOUBLE(val) => write!(f, "{}", val),
-            Symbol::BOOL(val) => write!(f, "{}", val),
+            Symbol::EXPR(expr) => write!(f, "{}", expr),
             Symbol::FUNC { return_type } => write!(f, "fn -> {}", return_type),
         }
     }
@@ -0,0 +1,634 @@
+use super::ast::{Ast, AstNode, ObjectMap};
+use super::span::{Span, Spanned};
+use std::error::Error;
+use std::fmt;
+
+/// violation of the Makeflow workflow schema. path is the JSON path of the offending value,
+/// e.g. $.rules[0].inputs[1]
+#[derive(Debug)]
+pub enum WorkflowErr {
+    // required key is absent from the object at path
+    MissingKey {
+        path: String,
+        key: &'static str,
+        span: Span,
+    },
+    // value of the wrong type
+    WrongType {
+        path: String,
+        expected: &'static str,
+        found: String,
+        span: Span,
+    },
+    // key that is not part of the schema
+    UnknownKey {
+        path: String,
+        span: Span,
+    },
+    // value of the right type that is not allowed, e.g. rule with both command and workflow
+    InvalidValue {
+        path: String,
+        reason: String,
+        span: Span,
+    },
+}
+
+impl Error for WorkflowErr {}
+
+impl fmt::Display for WorkflowErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            WorkflowErr::MissingKey { path, key, span } => {
+                write!(f, "{}: missing \"{}\" at {}", path, key, span)
+            }
+            WorkflowErr::WrongType {
+                path,
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "{}: expected {}, found {} at {}",
+                path, expected, found, span
+            ),
+            WorkflowErr::UnknownKey { path, span } => {
+                write!(f, "{}: unknown key at {}", path, span)
+            }
+            WorkflowErr::InvalidValue { path, reason, span } => {
+                write!(f, "{}: {} at {}", path, reason, span)
+            }
+        }
+    }
+}
+
+impl WorkflowErr {
+    /// JSON path of the offending value
+    pub fn path(&self) -> &str {
+        match &self {
+            WorkflowErr::MissingKey { path, .. }
+            | WorkflowErr::WrongType { path, .. }
+            | WorkflowErr::UnknownKey { path, .. }
+            | WorkflowErr::InvalidValue { path, .. } => path,
+        }
+    }
+
+    /// location of the offending value, for a missing key the object that lacks it
+    pub fn span(&self) -> &Span {
+        match &self {
+            WorkflowErr::MissingKey { span, .. }
+            | WorkflowErr::WrongType { span, .. }
+            | WorkflowErr::UnknownKey { span, .. }
+            | WorkflowErr::InvalidValue { span, .. } => span,
+        }
+    }
+
+    /// error message followed by the source snippet where the error is
+    pub fn render(&self) -> String {
+        format!("{}\n{}", self, self.span().render())
+    }
+}
+
+type Node = Spanned<AstNode>;
+
+// memory and disk are in MB
+const RESOURCE_NAMES: [&str; 4] = ["cores", "memory", "disk", "gpus"];
+
+/// check the evaluated document against the Makeflow workflow schema, every violation is reported
+pub fn validate(root: &Node) -> Vec<WorkflowErr> {
+    let mut errors = vec![];
+    let workflow = match expect_object(root, "$", &mut errors) {
+        Some(workflow) => workflow,
+        None => return errors,
+    };
+    if !workflow.contains_key("rules") {
+        errors.push(WorkflowErr::MissingKey {
+            path: String::from("$"),
+            key: "rules",
+            span: root.span.clone(),
+        });
+    }
+    for (key, value) in workflow {
+        let path = format!("$.{}", key);
+        match key.as_str() {
+            "rules" => validate_list(value, &path, &mut errors, validate_rule),
+            "categories" => validate_categories(value, &path, &mut errors),
+            "default_category" => check_type(value, &path, "string", &mut errors),
+            "environment" => validate_environment(value, &path, &mut errors),
+            // variables are not part of the workflow
+            "define" => (),
+            _ => errors.push(unknown_key(path, value)),
+        }
+    }
+    errors
+}
+
+fn validate_rule(rule: &Node, path: &str, errors: &mut Vec<WorkflowErr>) {
+    let fields = match expect_object(rule, path, errors) {
+        Some(fields) => fields,
+        None => return,
+    };
+    // a rule runs either a command or a sub-workflow
+    match (
+        fields.contains_key("command"),
+        fields.contains_key("workflow"),
+    ) {
+        (true, true) => errors.push(WorkflowErr::InvalidValue {
+            path: String::from(path),
+            reason: String::from("rule has both \"command\" and \"workflow\""),
+            span: rule.span.clone(),
+        }),
+        (false, false) => errors.push(WorkflowErr::InvalidValue {
+            path: String::from(path),
+            reason: String::from("rule has neither \"command\" nor \"workflow\""),
+            span: rule.span.clone(),
+        }),
+        _ => (),
+    }
+    for (key, value) in fields {
+        let path = format!("{}.{}", path, key);
+        match key.as_str() {
+            "command" | "workflow" | "category" => check_type(value, &path, "string", errors),
+            "inputs" | "outputs" => validate_list(value, &path, errors, validate_file),
+            "local_job" => check_type(value, &path, "boolean", errors),
+            "resources" => validate_resources(value, &path, errors),
+            "environment" => validate_environment(value, &path, errors),
+            // arguments of a sub-workflow
+            "args" => check_type(value, &path, "object", errors),
+            _ => errors.push(unknown_key(path, value)),
+        }
+    }
+}
+
+/// file is a name, or {"dag_name": ..., "task_name": ...} when it is renamed for the task
+fn validate_file(file: &Node, path: &str, errors: &mut Vec<WorkflowErr>) {
+    let fields = match &file.node {
+        AstNode::STRVAL(_) => return,
+        AstNode::OBJECT(fields) => fields,
+        other => {
+            errors.push(wrong_type(path, "string or object", other, &file.span));
+            return;
+        }
+    };
+    if !fields.contains_key("dag_name") {
+        errors.push(WorkflowErr::MissingKey {
+            path: String::from(path),
+            key: "dag_name",
+            span: file.span.clone(),
+        });
+    }
+    for (key, value) in fields {
+        let path = format!("{}.{}", path, key);
+        match key.as_str() {
+            "dag_name" | "task_name" => check_type(value, &path, "string", errors),
+            _ => errors.push(unknown_key(path, value)),
+        }
+    }
+}
+
+fn validate_categories(categories: &Node, path: &str, errors: &mut Vec<WorkflowErr>) {
+    let categories = match expect_object(categories, path, errors) {
+        Some(categories) => categories,
+        None => return,
+    };
+    for (name, category) in categories {
+        let path = format!("{}.{}", path, name);
+        let fields = match expect_object(category, &path, errors) {
+            Some(fields) => fields,
+            None => continue,
+        };
+        for (key, value) in fields {
+            let path = format!("{}.{}", path, key);
+            match key.as_str() {
+                "resources" => validate_resources(value, &path, errors),
+                "environment" => validate_environment(value, &path, errors),
+                _ => errors.push(unknown_key(path, value)),
+            }
+        }
+    }
+}
+
+/// resources are non-negative integers, e.g. {"cores": 1, "memory": 1024}
+fn validate_resources(resources: &Node, path: &str, errors: &mut Vec<WorkflowErr>) {
+    let resources = match expect_object(resources, path, errors) {
+        Some(resources) => resources,
+        None => return,
+    };
+    for (name, value) in resources {
+        let path = format!("{}.{}", path, name);
+        if !RESOURCE_NAMES.contains(&name.as_str()) {
+            errors.push(unknown_key(path, value));
+            continue;
+        }
+        match value.node {
+            AstNode::INTVAL(val) if val < 0 => errors.push(WorkflowErr::InvalidValue {
+                path,
+                reason: format!("resource {} is negative", val),
+                span: value.span.clone(),
+            }),
+            AstNode::INTVAL(_) => (),
+            ref other => errors.push(wrong_type(&path, "int", other, &value.span)),
+        }
+    }
+}
+
+/// environment variables and their values
+fn validate_environment(environment: &Node, path: &str, errors: &mut Vec<WorkflowErr>) {
+    if let Some(variables) = expect_object(environment, path, errors) {
+        for (name, value) in variables {
+            check_type(value, &format!("{}.{}", path, name), "string", errors);
+        }
+    }
+}
+
+fn validate_list(
+    list: &Node,
+    path: &str,
+    errors: &mut Vec<WorkflowErr>,
+    validate_item: fn(&Node, &str, &mut Vec<WorkflowErr>),
+) {
+    match &list.node {
+        AstNode::LIST(items) => {
+            for (i, item) in items.iter().enumerate() {
+                validate_item(item, &format!("{}[{}]", path, i), errors);
+            }
+        }
+        other => errors.push(wrong_type(path, "list", other, &list.span)),
+    }
+}
+
+fn expect_object<'a>(
+    node: &'a Node,
+    path: &str,
+    errors: &mut Vec<WorkflowErr>,
+) -> Option<&'a ObjectMap> {
+    match &node.node {
+        AstNode::OBJECT(fields) => Some(fields),
+        other => {
+            errors.push(wrong_type(path, "object", other, &node.span));
+            None
+        }
+    }
+}
+
+/// expected is a type name as in AstNode::type_name
+fn check_type(node: &Node, path: &str, expected: &'static str, errors: &mut Vec<WorkflowErr>) {
+    if node.type_name() != expected {
+        errors.push(wrong_type(path, expected, node, &node.span));
+    }
+}
+
+fn wrong_type(path: &str, expected: &'static str, found: &AstNode, span: &Span) -> WorkflowErr {
+    WorkflowErr::WrongType {
+        path: String::from(path),
+        expected,
+        found: String::from(found.type_name()),
+        span: span.clone(),
+    }
+}
+
+fn unknown_key(path: String, value: &Node) -> WorkflowErr {
+    WorkflowErr::UnknownKey {
+        path,
+        span: value.span.clone(),
+    }
+}
+
+/// Makeflow workflow, the typed form of a valid evaluated document
+#[derive(Clone, Default)]
+pub struct Workflow {
+    pub rules: Vec<Rule>,
+    pub categories: Vec<Category>,
+    pub default_category: Option<String>,
+    pub environment: Environment,
+    // variables of the document, kept so that converting back gives the same document
+    pub define: Option<ObjectMap>,
+}
+
+#[derive(Clone)]
+pub struct Rule {
+    pub job: Job,
+    pub inputs: Vec<FileSpec>,
+    pub outputs: Vec<FileSpec>,
+    // run on the submitting host instead of a remote worker
+    pub local_job: bool,
+    pub category: Option<String>,
+    pub resources: Resources,
+    pub environment: Environment,
+}
+
+/// what a rule runs
+#[derive(Clone)]
+pub enum Job {
+    // shell command
+    Command(String),
+    // sub-workflow with its arguments
+    Workflow {
+        path: String,
+        args: Option<ObjectMap>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileSpec {
+    // name in the workflow directory
+    pub dag_name: String,
+    // name in the task sandbox, None if it is the same as dag_name
+    pub task_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Category {
+    pub name: String,
+    pub resources: Resources,
+    pub environment: Environment,
+}
+
+/// resources of a task, None if not specified. memory and disk are in MB
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Resources {
+    pub cores: Option<i64>,
+    pub memory: Option<i64>,
+    pub disk: Option<i64>,
+    pub gpus: Option<i64>,
+}
+
+/// environment variables in the order they are defined
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Environment {
+    pub vars: Vec<(String, String)>,
+}
+
+impl Workflow {
+    /// typed workflow from an evaluated document, the errors are those of validate
+    pub fn from_ast(root: &Node) -> Result<Workflow, Vec<WorkflowErr>> {
+        let errors = validate(root);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        // the document is valid, every value below has the type the schema requires
+        let mut workflow = Workflow::default();
+        for (key, value) in object(root) {
+            match key.as_str() {
+                "rules" => {
+                    workflow.rules = list(value)
+                        .iter()
+                        .map(|rule| Rule::from_ast(rule))
+                        .collect()
+                }
+                "categories" => {
+                    workflow.categories = object(value)
+                        .iter()
+                        .map(|(name, category)| Category::from_ast(name, category))
+                        .collect()
+                }
+                "default_category" => workflow.default_category = Some(string(value)),
+                "environment" => workflow.environment = Environment::from_ast(value),
+                "define" => workflow.define = Some(object(value).clone()),
+                _ => (),
+            }
+        }
+        Ok(workflow)
+    }
+
+    /// document of the workflow, the inverse of from_ast. values that are the default are left out
+    pub fn to_ast(&self) -> Ast {
+        let mut fields = ObjectMap::new();
+        if let Some(define) = &self.define {
+            fields.insert(
+                String::from("define"),
+                AstNode::OBJECT(define.clone()).boxed(),
+            );
+        }
+        if !self.categories.is_empty() {
+            let categories = self
+                .categories
+                .iter()
+                .map(|category| (category.name.clone(), category.to_ast()))
+                .collect();
+            fields.insert(
+                String::from("categories"),
+                AstNode::OBJECT(categories).boxed(),
+            );
+        }
+        if let Some(name) = &self.default_category {
+            fields.insert(String::from("default_category"), str_node(name));
+        }
+        if !self.environment.vars.is_empty() {
+            fields.insert(String::from("environment"), self.environment.to_ast());
+        }
+        let rules = self.rules.iter().map(Rule::to_ast).collect();
+        fields.insert(String::from("rules"), AstNode::LIST(rules).boxed());
+        AstNode::OBJECT(fields).boxed()
+    }
+}
+
+impl Rule {
+    fn from_ast(rule: &Node) -> Rule {
+        let fields = object(rule);
+        let job = match (fields.get("command"), fields.get("workflow")) {
+            (Some(command), _) => Job::Command(string(command)),
+            (None, Some(path)) => Job::Workflow {
+                path: string(path),
+                args: fields.get("args").map(|args| object(args).clone()),
+            },
+            (None, None) => panic!("rule without command or workflow"),
+        };
+        let files = |key| match fields.get(key) {
+            Some(files) => list(files)
+                .iter()
+                .map(|file| FileSpec::from_ast(file))
+                .collect(),
+            None => vec![],
+        };
+        Rule {
+            job,
+            inputs: files("inputs"),
+            outputs: files("outputs"),
+            local_job: matches!(
+                fields.get("local_job").map(|val| &val.node),
+                Some(AstNode::BOOLVAL(true))
+            ),
+            category: fields.get("category").map(|category| string(category)),
+            resources: fields
+                .get("resources")
+                .map(|val| Resources::from_ast(val))
+                .unwrap_or_default(),
+            environment: fields
+                .get("environment")
+                .map(|val| Environment::from_ast(val))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn to_ast(&self) -> Ast {
+        let mut fields = ObjectMap::new();
+        match &self.job {
+            Job::Command(command) => {
+                fields.insert(String::from("command"), str_node(command));
+            }
+            Job::Workflow { path, args } => {
+                fields.insert(String::from("workflow"), str_node(path));
+                if let Some(args) = args {
+                    fields.insert(String::from("args"), AstNode::OBJECT(args.clone()).boxed());
+                }
+            }
+        }
+        for (key, files) in [("inputs", &self.inputs), ("outputs", &self.outputs)] {
+            if !files.is_empty() {
+                let files = files.iter().map(FileSpec::to_ast).collect();
+                fields.insert(String::from(key), AstNode::LIST(files).boxed());
+            }
+        }
+        if self.local_job {
+            fields.insert(String::from("local_job"), AstNode::BOOLVAL(true).boxed());
+        }
+        if let Some(category) = &self.category {
+            fields.insert(String::from("category"), str_node(category));
+        }
+        if self.resources != Resources::default() {
+            fields.insert(String::from("resources"), self.resources.to_ast());
+        }
+        if !self.environment.vars.is_empty() {
+            fields.insert(String::from("environment"), self.environment.to_ast());
+        }
+        AstNode::OBJECT(fields).boxed()
+    }
+}
+
+impl FileSpec {
+    fn from_ast(file: &Node) -> FileSpec {
+        match &file.node {
+            AstNode::STRVAL(name) => FileSpec {
+                dag_name: name.clone(),
+                task_name: None,
+            },
+            AstNode::OBJECT(fields) => FileSpec {
+                dag_name: string(&fields["dag_name"]),
+                task_name: fields.get("task_name").map(|name| string(name)),
+            },
+            _ => panic!("file is not a string or object"),
+        }
+    }
+
+    /// plain name unless the file is renamed for the task
+    fn to_ast(&self) -> Ast {
+        match &self.task_name {
+            None => str_node(&self.dag_name),
+            Some(task_name) => {
+                let mut fields = ObjectMap::new();
+                fields.insert(String::from("dag_name"), str_node(&self.dag_name));
+                fields.insert(String::from("task_name"), str_node(task_name));
+                AstNode::OBJECT(fields).boxed()
+            }
+        }
+    }
+}
+
+impl Category {
+    fn from_ast(name: &str, category: &Node) -> Category {
+        let fields = object(category);
+        Category {
+            name: String::from(name),
+            resources: fields
+                .get("resources")
+                .map(|val| Resources::from_ast(val))
+                .unwrap_or_default(),
+            environment: fields
+                .get("environment")
+                .map(|val| Environment::from_ast(val))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn to_ast(&self) -> Ast {
+        let mut fields = ObjectMap::new();
+        if self.resources != Resources::default() {
+            fields.insert(String::from("resources"), self.resources.to_ast());
+        }
+        if !self.environment.vars.is_empty() {
+            fields.insert(String::from("environment"), self.environment.to_ast());
+        }
+        AstNode::OBJECT(fields).boxed()
+    }
+}
+
+impl Resources {
+    fn from_ast(resources: &Node) -> Resources {
+        let fields = object(resources);
+        let int = |name| match fields.get(name).map(|val| &val.node) {
+            Some(AstNode::INTVAL(val)) => Some(*val),
+            _ => None,
+        };
+        Resources {
+            cores: int("cores"),
+            memory: int("memory"),
+            disk: int("disk"),
+            gpus: int("gpus"),
+        }
+    }
+
+    fn to_ast(&self) -> Ast {
+        let values = [self.cores, self.memory, self.disk, self.gpus];
+        let fields = RESOURCE_NAMES
+            .iter()
+            .zip(values)
+            .filter_map(|(name, val)| Some((String::from(*name), AstNode::INTVAL(val?).boxed())))
+            .collect();
+        AstNode::OBJECT(fields).boxed()
+    }
+}
+
+impl Environment {
+    fn from_ast(environment: &Node) -> Environment {
+        Environment {
+            vars: object(environment)
+                .iter()
+                .map(|(name, value)| (name.clone(), string(value)))
+                .collect(),
+        }
+    }
+
+    fn to_ast(&self) -> Ast {
+        let vars = self
+            .vars
+            .iter()
+            .map(|(name, value)| (name.clone(), str_node(value)))
+            .collect();
+        AstNode::OBJECT(vars).boxed()
+    }
+
+    /// value of the variable
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(var, _)| var == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// accessors of values that are already validated
+
+fn object(node: &Node) -> &ObjectMap {
+    match &node.node {
+        AstNode::OBJECT(fields) => fields,
+        _ => panic!("validated value is not an object"),
+    }
+}
+
+fn list(node: &Node) -> &Vec<Ast> {
+    match &node.node {
+        AstNode::LIST(items) => items,
+        _ => panic!("validated value is not a list"),
+    }
+}
+
+fn string(node: &Node) -> String {
+    match &node.node {
+        AstNode::STRVAL(val) => val.clone(),
+        _ => panic!("validated value is not a string"),
+    }
+}
+
+fn str_node(val: &str) -> Ast {
+    AstNode::STRVAL(String::from(val)).boxed()
+}
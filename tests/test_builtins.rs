@@ -0,0 +1,175 @@
+extern crate jx2json;
+use jx2json::ast::{Ast, AstNode};
+use jx2json::eval::{self, EvalErr};
+use jx2json::parser;
+use jx2json::scanner;
+use jx2json::symbol_tab::SymbolTable;
+use std::time::{Duration, Instant};
+
+#[test]
+fn builtin_range() {
+    assert_eq!("[ 0, 1, 2 ]", eval_str("range(3)"));
+    assert_eq!("[ 2, 4, 6 ]", eval_str("range(2, 8, 2)"));
+    assert_eq!("[ 3, 2, 1 ]", eval_str("range(3, 0, -1)"));
+    assert!(matches!(
+        eval_expr("range(1, 2, 0)"),
+        Err(EvalErr::Func { .. })
+    ));
+    assert!(matches!(
+        eval_expr("range(\"3\")"),
+        Err(EvalErr::Func { .. })
+    ));
+}
+
+#[test]
+fn builtin_format() {
+    assert_eq!(
+        "out.5.txt|  42|-7   |007|+3",
+        eval_str("format(\"out.%d.txt|%4d|%-5i|%03d|%+d\", 5, 42, -7, 7, 3)")
+    );
+    assert_eq!(
+        "3.14|1.500000e+03|0.0001|1e+06|50%",
+        eval_str("format(\"%.2f|%e|%g|%g|%d%%\", 3.14159, 1500, 0.0001, 1000000.0, 50)")
+    );
+    assert_eq!("[ab   ]", eval_str("format(\"[%-5.2s]\", \"abc\")"));
+    // wrong type, too few and too many arguments
+    assert!(matches!(
+        eval_expr("format(\"%d\", \"a\")"),
+        Err(EvalErr::Func { .. })
+    ));
+    assert!(matches!(
+        eval_expr("format(\"%d %d\", 1)"),
+        Err(EvalErr::Func { .. })
+    ));
+    assert!(matches!(
+        eval_expr("format(\"%d\", 1, 2)"),
+        Err(EvalErr::Func { .. })
+    ));
+}
+
+#[test]
+fn builtin_string_and_list() {
+    assert_eq!("a b c", eval_str("join([\"a\", \"b\", \"c\"])"));
+    assert_eq!("a,b", eval_str("join([\"a\", \"b\"], \",\")"));
+    assert_eq!("3", eval_str("len([1, 2, x for x in range(1)])"));
+    assert!(matches!(eval_expr("ceil(1.5)").unwrap().node, AstNode::DOUBLEVAL(val) if val == 2.0));
+    assert!(
+        matches!(eval_expr("floor(-1.5)").unwrap().node, AstNode::DOUBLEVAL(val) if val == -2.0)
+    );
+    assert_eq!("4", eval_str("floor(4)"));
+    assert_eq!("c.txt", eval_str("basename(\"/a/b/c.txt\")"));
+    assert_eq!("c", eval_str("basename(\"/a/b/c.txt\", \".txt\")"));
+    assert_eq!("/a/b", eval_str("dirname(\"/a/b/c.txt\")"));
+    assert_eq!(".", eval_str("dirname(\"c.txt\")"));
+    assert_eq!("/", eval_str("dirname(\"/c.txt\")"));
+    assert_eq!("'it'\\''s'", eval_str("escape(\"it's\")"));
+    assert!(matches!(
+        eval_expr("join([1, 2])"),
+        Err(EvalErr::Func { .. })
+    ));
+    assert!(matches!(
+        eval_expr("len(\"abc\")"),
+        Err(EvalErr::Func { .. })
+    ));
+}
+
+#[test]
+fn builtin_object() {
+    assert_eq!("[ a ]", eval_str("keys({\"a\": 1})"));
+    assert_eq!("[ 1 ]", eval_str("values({\"a\": 1})"));
+    assert_eq!("[ [ a, 1 ] ]", eval_str("items({\"a\": 1})"));
+    assert!(matches!(eval_expr("keys([1])"), Err(EvalErr::Func { .. })));
+}
+
+#[test]
+fn builtin_like() {
+    assert_eq!("true", eval_str("like(\"sample.fastq\", \"[.]fastq$\")"));
+    assert_eq!("false", eval_str("like(\"sample.fasta\", \"[.]fastq$\")"));
+    assert_eq!("true", eval_str("like(\"abc123\", \"^[a-c]+[0-9]{2,3}$\")"));
+    assert_eq!("true", eval_str("like(\"cat\", \"dog|ca(t|r)\")"));
+    assert!(matches!(
+        eval_expr("like(\"a\", \"(a\")"),
+        Err(EvalErr::Func { .. })
+    ));
+    assert!(matches!(
+        eval_expr("like(\"a\", \"a{3,1}\")"),
+        Err(EvalErr::Func { .. })
+    ));
+    // nested bounds multiply the size of the compiled regex
+    match eval_expr("like(\"a\", \"((a{255}){255}){255}\")") {
+        Err(err) => assert_eq!("like(): regex is too large", err.to_string()),
+        Ok(val) => panic!("{} should be an error", val),
+    }
+}
+
+#[test]
+fn builtin_like_nested_repeat() {
+    // a backtracking matcher takes exponential time on these
+    let start = Instant::now();
+    assert_eq!(
+        "false",
+        eval_str("like(\"aaaaaaaaaaaaaaaaaaaaaaaaaaaaa!\", \"(a*)*b\")")
+    );
+    let input = "a".repeat(2000);
+    assert_eq!(
+        "false",
+        eval_str(&format!("like(\"{}!\", \"^(a|aa)+(a?){{10}}b\")", input))
+    );
+    assert_eq!(
+        "true",
+        eval_str(&format!("like(\"{}\", \"^(a*)*$\")", input))
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn builtin_select_project_template() {
+    let people = "[{\"name\": \"a\", \"age\": 20}, {\"name\": \"b\", \"age\": 30}]";
+    assert_eq!(
+        "[ { \"name\" : b } ]",
+        eval_str(&format!(
+            "project({{\"name\": name}}, select(age > 25, {}))",
+            people
+        ))
+    );
+    assert_eq!("[ a, b ]", eval_str(&format!("project(name, {})", people)));
+    assert!(matches!(
+        eval_expr(&format!("select(age, {})", people)),
+        Err(EvalErr::Func { .. })
+    ));
+
+    assert_eq!(
+        "out.1.txt",
+        eval_str("template(\"{prefix}.{n}.txt\", {\"prefix\": \"out\", \"n\": 1})")
+    );
+    assert!(matches!(
+        eval_expr("template(\"{missing}\")"),
+        Err(EvalErr::Func { .. })
+    ));
+}
+
+#[test]
+fn builtin_undefined() {
+    match eval_expr("nosuchfunc(1)") {
+        Err(err) => assert_eq!("nosuchfunc(): undefined function", err.to_string()),
+        Ok(val) => panic!("{} should be an error", val),
+    }
+}
+
+/// evaluate a single expression, the result is wrapped in a document
+fn eval_expr(expr: &str) -> Result<Ast, EvalErr> {
+    let src = format!("{{ \"x\": {} }}", expr);
+    let tokens = scanner::scan_token(src).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    match eval::eval(&result, &SymbolTable::new())?.node {
+        AstNode::OBJECT(mut keyval_pairs) => Ok(keyval_pairs.remove("x").unwrap()),
+        other => panic!("{} should be OBJECT", other),
+    }
+}
+
+fn eval_str(expr: &str) -> String {
+    match eval_expr(expr) {
+        Ok(val) => val.to_string(),
+        Err(err) => panic!("{}: {}", expr, err),
+    }
+}
@@ -0,0 +1,464 @@
+extern crate jx2json;
+use jx2json::ast::{Ast, AstNode, CompreClause, ObjectMap};
+use jx2json::eval::{self, EvalErr, EvalOptions};
+use jx2json::parser::{self, DuplicateKeys, ParseOptions, ParserResult};
+use jx2json::scanner;
+use jx2json::symbol_tab::{Symbol, SymbolTable};
+
+#[test]
+fn eval_int_arith() {
+    let expr = AstNode::MUL {
+        left: AstNode::ADD {
+            left: int(1),
+            right: int(2),
+        }
+        .boxed(),
+        right: int(4),
+    };
+    match &eval_expr(expr, &SymbolTable::new()).unwrap().node {
+        AstNode::INTVAL(val) => assert_eq!(12, *val),
+        other => panic!("{} should be INTVAL", other),
+    }
+}
+
+#[test]
+fn eval_double_promotion() {
+    let expr = AstNode::DIV {
+        left: int(3),
+        right: AstNode::DOUBLEVAL(2.0).boxed(),
+    };
+    match &eval_expr(expr, &SymbolTable::new()).unwrap().node {
+        AstNode::DOUBLEVAL(val) => assert_eq!(1.5, *val),
+        other => panic!("{} should be DOUBLEVAL", other),
+    }
+}
+
+#[test]
+fn eval_concat() {
+    let expr = AstNode::ADD {
+        left: str("out"),
+        right: str(".txt"),
+    };
+    match &eval_expr(expr, &SymbolTable::new()).unwrap().node {
+        AstNode::STRVAL(val) => assert_eq!("out.txt", val),
+        other => panic!("{} should be STRVAL", other),
+    }
+
+    let expr = AstNode::ADD {
+        left: AstNode::LIST(vec![int(1)]).boxed(),
+        right: AstNode::LIST(vec![int(2), int(3)]).boxed(),
+    };
+    match &eval_expr(expr, &SymbolTable::new()).unwrap().node {
+        AstNode::LIST(list) => assert_eq!(3, list.len()),
+        other => panic!("{} should be LIST", other),
+    }
+}
+
+#[test]
+fn eval_object_merge() {
+    let mut left = ObjectMap::new();
+    left.insert(String::from("cores"), int(1));
+    left.insert(String::from("memory"), int(1024));
+    let mut right = ObjectMap::new();
+    right.insert(String::from("cores"), int(4));
+    let expr = AstNode::ADD {
+        left: AstNode::OBJECT(left).boxed(),
+        right: AstNode::OBJECT(right).boxed(),
+    };
+    match &eval_expr(expr, &SymbolTable::new()).unwrap().node {
+        AstNode::OBJECT(obj) => {
+            assert_eq!(2, obj.len());
+            assert!(matches!(obj["cores"].node, AstNode::INTVAL(4)));
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+}
+
+#[test]
+fn eval_var_and_compre() {
+    let mut ctx = SymbolTable::new();
+    ctx.insert(
+        String::from("files"),
+        Symbol::EXPR(AstNode::LIST(vec![str("a"), str("b")]).boxed()),
+    );
+    let compre = AstNode::COMPRE {
+        expr: AstNode::ADD {
+            left: AstNode::VAR(String::from("x")).boxed(),
+            right: str(".txt"),
+        }
+        .boxed(),
+        clauses: vec![CompreClause {
+            var: String::from("x"),
+            iter_expr: AstNode::VAR(String::from("files")).boxed(),
+            conds: vec![],
+        }],
+    };
+    let expr = AstNode::LIST(vec![str("first"), compre.boxed()]);
+    match &eval_expr(expr, &ctx).unwrap().node {
+        AstNode::LIST(list) => {
+            let names: Vec<String> = list.iter().map(|node| node.to_string()).collect();
+            assert_eq!(vec!["first", "a.txt", "b.txt"], names);
+        }
+        other => panic!("{} should be LIST", other),
+    }
+}
+
+#[test]
+fn eval_errors() {
+    let expr = AstNode::VAR(String::from("undefined"));
+    assert!(matches!(
+        eval_expr(expr, &SymbolTable::new()),
+        Err(EvalErr::Undefined(_))
+    ));
+    let expr = AstNode::DIV {
+        left: int(1),
+        right: int(0),
+    };
+    assert!(matches!(
+        eval_expr(expr, &SymbolTable::new()),
+        Err(EvalErr::Arith(_))
+    ));
+    let expr = AstNode::ADD {
+        left: int(1),
+        right: str("a"),
+    };
+    assert!(matches!(
+        eval_expr(expr, &SymbolTable::new()),
+        Err(EvalErr::Type(_))
+    ));
+    let expr = AstNode::MUL {
+        left: int(i64::MAX),
+        right: int(2),
+    };
+    assert!(matches!(
+        eval_expr(expr, &SymbolTable::new()),
+        Err(EvalErr::Arith(_))
+    ));
+}
+
+#[test]
+fn eval_computed_keys() {
+    let root = eval_src(
+        "{ \"define\": { \"name\": \"s\", \"n\": 2 }, \"x\": { name: 1, format(\"o.%d\", n): 2, name + \"2\": 3 } }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert_eq!(
+                "{ \"s\" : 1, \"o.2\" : 2, \"s2\" : 3 }",
+                keyval_pairs["x"].to_string()
+            )
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+    // key must be a string
+    match eval_src("{ \"x\": { 1 + 1: 2 } }", &SymbolTable::new()) {
+        Err(err @ EvalErr::Type(_)) => {
+            assert_eq!("object key 1 + 1 is int, expect string", err.to_string())
+        }
+        _ => panic!("should be Type error"),
+    }
+}
+
+#[test]
+fn eval_duplicate_computed_keys() {
+    let src = "{\"define\": {\"n\": \"a\"}, n: 1, \"a\": 2, n + \"b\": 3, \"a\" + \"b\": 4}";
+    let eval_policy = |duplicate_keys| {
+        let tokens = scanner::scan_source(String::from(src), "wf.jx").unwrap();
+        let options = ParseOptions { duplicate_keys };
+        let (result, errors) = parser::parse_tokens_recover(tokens, options);
+        assert!(errors.is_empty());
+        eval::eval_all(
+            &result.unwrap(),
+            &SymbolTable::new(),
+            EvalOptions { duplicate_keys },
+        )
+    };
+
+    // the error points at the repeated key and the one it repeats
+    let evaluated = eval_policy(DuplicateKeys::ERROR);
+    match &evaluated.root.node {
+        AstNode::ERROR(err @ EvalErr::DuplicateKey { .. }) => {
+            assert_eq!(
+                "duplicate key \"a\", first defined at wf.jx:1:24",
+                err.to_string()
+            );
+            assert_eq!((1, 30), (evaluated.root.span.line, evaluated.root.span.col));
+        }
+        other => panic!("{} should be ERROR", other),
+    }
+    assert_eq!(1, evaluated.errors.len());
+
+    for (duplicate_keys, expected) in [
+        (DuplicateKeys::KEEPFIRST, "{ \"a\" : 1, \"ab\" : 3 }"),
+        (DuplicateKeys::KEEPLAST, "{ \"a\" : 2, \"ab\" : 4 }"),
+    ] {
+        let evaluated = eval_policy(duplicate_keys);
+        let mut root = evaluated.root;
+        match &mut root.node {
+            AstNode::OBJECT(keyval_pairs) => {
+                keyval_pairs.remove("define");
+            }
+            other => panic!("{} should be OBJECT", other),
+        }
+        assert_eq!(expected, root.to_string());
+        assert_eq!(2, evaluated.warnings.len());
+        assert!(evaluated.errors.is_empty());
+    }
+}
+
+#[test]
+fn eval_lookup_slice() {
+    let root = eval_src(
+        "{ \"define\": { \"l\": [1, 2, 3, 4], \"o\": { \"a\": [5] } }, \"x\": [l[0], l[-1], o[\"a\"][0]], \"s\": [l[1:3], l[-2:], l[:10], l[3:1]] }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert_eq!("[ 1, 4, 5 ]", keyval_pairs["x"].to_string());
+            assert_eq!(
+                "[ [ 2, 3 ], [ 3, 4 ], [ 1, 2, 3, 4 ], [  ] ]",
+                keyval_pairs["s"].to_string()
+            );
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+    match eval_src("{ \"x\": [1][1] }", &SymbolTable::new()) {
+        Err(err @ EvalErr::Lookup(_)) => {
+            assert_eq!("index 1 out of range for list of length 1", err.to_string())
+        }
+        _ => panic!("should be Lookup error"),
+    }
+    assert!(matches!(
+        eval_src("{ \"x\": { \"a\": 1 }[\"b\"] }", &SymbolTable::new()),
+        Err(EvalErr::Lookup(_))
+    ));
+    assert!(matches!(
+        eval_src("{ \"x\": [1][\"a\"] }", &SymbolTable::new()),
+        Err(EvalErr::Type(_))
+    ));
+}
+
+#[test]
+fn eval_conditional_short_circuit() {
+    // the untaken branch and the decided right operand would fail if evaluated
+    let root = eval_src(
+        "{ \"define\": { \"l\": [] }, \"c\": l[0] if len(l) > 0 else \"none\", \"and\": len(l) > 0 and l[0] > 1, \"or\": true or undefined }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert_eq!("none", keyval_pairs["c"].to_string());
+            assert!(matches!(keyval_pairs["and"].node, AstNode::BOOLVAL(false)));
+            assert!(matches!(keyval_pairs["or"].node, AstNode::BOOLVAL(true)));
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+    match eval_src("{ \"x\": 1 if 0 else 2 }", &SymbolTable::new()) {
+        Err(err @ EvalErr::Type(_)) => {
+            assert_eq!("condition 0 is int, expect boolean", err.to_string())
+        }
+        _ => panic!("should be Type error"),
+    }
+    // right operand is still checked when it decides the result
+    assert!(matches!(
+        eval_src("{ \"x\": true and 1 }", &SymbolTable::new()),
+        Err(EvalErr::Type(_))
+    ));
+}
+
+#[test]
+fn eval_error_values() {
+    let src = "{ \"define\": { \"n\": 1 / 0 }, \"a\": format(\"%d\", n + 1), \"b\": [1, undefined], \"c\": 1 if true else nosuch }";
+    let tokens = scanner::scan_token(String::from(src)).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    let evaluated = eval::eval_all(&result, &SymbolTable::new(), EvalOptions::default());
+    // the error passes through + and format() unchanged, it keeps the span of 1 / 0
+    match &evaluated.root.node {
+        AstNode::ERROR(err @ EvalErr::Arith(_)) => {
+            assert_eq!("division by zero", err.to_string());
+            assert_eq!((1, 20), (evaluated.root.span.line, evaluated.root.span.col));
+        }
+        other => panic!("{} should be ERROR", other),
+    }
+    // the untaken branch raises nothing
+    assert_eq!(2, evaluated.errors.len());
+    assert!(matches!(
+        evaluated.errors[1].node,
+        AstNode::ERROR(EvalErr::Undefined(_))
+    ));
+    assert_eq!(
+        "error(\"undefined symbol undefined\")",
+        evaluated.errors[1].to_string()
+    );
+    // an error in a comprehension is located at the comprehension, not the list around it
+    let tokens = scanner::scan_token(String::from("{ \"x\": [1, y for y in 3] }")).unwrap();
+    let compre = parser::parse_tokens(tokens).unwrap();
+    let evaluated = eval::eval_all(&compre, &SymbolTable::new(), EvalOptions::default());
+    assert_eq!(1, evaluated.errors.len());
+    let err = &evaluated.errors[0];
+    assert!(matches!(err.node, AstNode::ERROR(EvalErr::Type(_))));
+    assert_eq!((1, 12), (err.span.line, err.span.col));
+    assert_eq!((1, 12), (evaluated.root.span.line, evaluated.root.span.col));
+
+    // eval reports the error the document failed with
+    assert!(matches!(
+        eval::eval(&result, &SymbolTable::new()),
+        Err(EvalErr::Arith(_))
+    ));
+}
+
+#[test]
+fn eval_null() {
+    let root = eval_src(
+        "{ \"eq\": null == null, \"ne\": null != 0, \"add\": 1 + null * 2, \"list\": [null] }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert!(matches!(keyval_pairs["eq"].node, AstNode::BOOLVAL(true)));
+            assert!(matches!(keyval_pairs["ne"].node, AstNode::BOOLVAL(true)));
+            assert!(keyval_pairs["add"].is_null());
+            assert_eq!("[ null ]", keyval_pairs["list"].to_string());
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+    // null can only be compared for equality
+    assert!(matches!(
+        eval_src("{ \"x\": null < 1 }", &SymbolTable::new()),
+        Err(EvalErr::Type(_))
+    ));
+}
+
+#[test]
+fn eval_int64() {
+    // memory size in bytes does not fit in 32 bits
+    let root = eval_src(
+        "{ \"mem\": 16 * 1024 * 1024 * 1024, \"min\": -9223372036854775808, \"e\": 1e3 + 1 }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert!(matches!(
+                keyval_pairs["mem"].node,
+                AstNode::INTVAL(17179869184)
+            ));
+            assert!(matches!(
+                keyval_pairs["min"].node,
+                AstNode::INTVAL(i64::MIN)
+            ));
+            assert!(matches!(keyval_pairs["e"].node, AstNode::DOUBLEVAL(val) if val == 1001.0));
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+    assert!(matches!(
+        eval_src("{ \"x\": -(-9223372036854775808) }", &SymbolTable::new()),
+        Err(EvalErr::Arith(_))
+    ));
+}
+
+#[test]
+fn eval_define() {
+    let root = eval_src(
+        "{
+            \"define\": { \"out\": prefix + \".txt\", \"prefix\": \"out\" + n, \"n\": \"1\" },
+            \"rules\": [ { \"outputs\": [ out ] } ]
+        }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    let rules = match &root.node {
+        AstNode::OBJECT(keyval_pairs) => &keyval_pairs["rules"],
+        _ => panic!("{} should be OBJECT", root),
+    };
+    assert_eq!("[ { \"outputs\" : [ out1.txt ] } ]", rules.to_string());
+}
+
+#[test]
+fn eval_define_overridden_by_ctx() {
+    let mut ctx = SymbolTable::new();
+    ctx.insert(String::from("n"), Symbol::EXPR(int(2)));
+    let root = eval_src(
+        "{ \"define\": { \"n\": 1, \"m\": n * 10 }, \"m\": m }",
+        &ctx,
+    )
+    .unwrap();
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert!(matches!(keyval_pairs["m"].node, AstNode::INTVAL(20)))
+        }
+        _ => panic!("{} should be OBJECT", root),
+    }
+}
+
+#[test]
+fn eval_define_cycle() {
+    let result = eval_src(
+        "{ \"define\": { \"a\": b + 1, \"b\": c, \"c\": a }, \"x\": a }",
+        &SymbolTable::new(),
+    );
+    match result {
+        Err(EvalErr::Cycle(names)) => {
+            assert_eq!(4, names.len());
+            assert_eq!(names.first(), names.last());
+        }
+        _ => panic!("should be cyclic definition"),
+    }
+}
+
+#[test]
+fn eval_nested_compre() {
+    let root = eval_src(
+        "{
+            \"define\": { \"xs\": [1, 2, 3], \"x\": \"unused\" },
+            \"pairs\": [ [x, y] for x in xs if x > 1 for y in [z * 10 for z in xs] if y != x * 10 ],
+            \"x\": x
+        }",
+        &SymbolTable::new(),
+    )
+    .unwrap();
+    let keyval_pairs = match &root.node {
+        AstNode::OBJECT(keyval_pairs) => keyval_pairs,
+        _ => panic!("{} should be OBJECT", root),
+    };
+    assert_eq!(
+        "[ [ 2, 10 ], [ 2, 30 ], [ 3, 10 ], [ 3, 20 ] ]",
+        keyval_pairs["pairs"].to_string()
+    );
+    // loop variable does not leak out of the comprehension
+    assert_eq!("unused", keyval_pairs["x"].to_string());
+
+    let result = eval_src(
+        "{ \"xs\": [ x for x in [1, 2] if x ] }",
+        &SymbolTable::new(),
+    );
+    assert!(matches!(result, Err(EvalErr::Type(_))));
+}
+
+fn eval_expr(expr: AstNode, ctx: &SymbolTable) -> Result<Ast, EvalErr> {
+    let result = ParserResult {
+        root: expr.boxed(),
+        tab: SymbolTable::new(),
+        warnings: vec![],
+    };
+    eval::eval(&result, ctx)
+}
+
+fn eval_src(src: &str, ctx: &SymbolTable) -> Result<Ast, EvalErr> {
+    let tokens = scanner::scan_token(String::from(src)).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    eval::eval(&result, ctx)
+}
+
+fn int(val: i64) -> Ast {
+    AstNode::INTVAL(val).boxed()
+}
+
+fn str(val: &str) -> Ast {
+    AstNode::STRVAL(String::from(val)).boxed()
+}
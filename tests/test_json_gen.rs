@@ -0,0 +1,96 @@
+extern crate jx2json;
+use jx2json::ast::{AstNode, ObjectMap};
+use jx2json::json_gen;
+use jx2json::symbol_tab::SymbolTable;
+use jx2json::{eval, parser, scanner};
+
+#[test]
+fn gen_scalars() {
+    assert_eq!("1", json_gen::gen_json(&AstNode::INTVAL(1)).unwrap());
+    assert_eq!("-42", json_gen::gen_json(&AstNode::INTVAL(-42)).unwrap());
+    assert_eq!("1.0", json_gen::gen_json(&AstNode::DOUBLEVAL(1.0)).unwrap());
+    assert_eq!("2.5", json_gen::gen_json(&AstNode::DOUBLEVAL(2.5)).unwrap());
+    assert_eq!("true", json_gen::gen_json(&AstNode::BOOLVAL(true)).unwrap());
+    assert_eq!("null", json_gen::gen_json(&AstNode::NULLVAL).unwrap());
+}
+
+#[test]
+fn gen_str_escape() {
+    let node = AstNode::STRVAL(String::from("echo \"a\\b\"\n\t\u{1}"));
+    assert_eq!(
+        "\"echo \\\"a\\\\b\\\"\\n\\t\\u0001\"",
+        json_gen::gen_json(&node).unwrap()
+    );
+}
+
+#[test]
+fn gen_str_escape_round_trip() {
+    // escapes decoded by the scanner are encoded again in the output
+    let src = r#"{"cmd":"echo \"a\\b\"\n\tcaf\u00e9 \ud83d\ude00"}"#;
+    let tokens = scanner::scan_token(String::from(src)).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    assert_eq!(
+        "{\"cmd\":\"echo \\\"a\\\\b\\\"\\n\\tcaf\u{e9} \u{1f600}\"}",
+        json_gen::gen_json(&result.root).unwrap()
+    );
+}
+
+#[test]
+fn gen_key_order() {
+    // keys keep the source order, also through evaluation
+    let src = r#"{"z": 1, "a": {"y": 2, "b": 3} + {"a": 4, "y": 5}, "m": [{"d": 1, "c": 2}]}"#;
+    let tokens = scanner::scan_token(String::from(src)).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    let mut root = eval::eval(&result, &SymbolTable::new()).unwrap();
+    assert_eq!(
+        r#"{"z":1,"a":{"y":5,"b":3,"a":4},"m":[{"d":1,"c":2}]}"#,
+        json_gen::gen_json(&root).unwrap()
+    );
+    root.sort_keys();
+    assert_eq!(
+        r#"{"a":{"a":4,"b":3,"y":5},"m":[{"c":2,"d":1}],"z":1}"#,
+        json_gen::gen_json(&root).unwrap()
+    );
+
+    let mut map = ObjectMap::new();
+    map.insert(String::from("b"), AstNode::INTVAL(1).boxed());
+    map.insert(String::from("a"), AstNode::INTVAL(2).boxed());
+    map.insert(String::from("c"), AstNode::INTVAL(3).boxed());
+    map.remove("b");
+    map.insert(String::from("b"), AstNode::INTVAL(4).boxed());
+    let keys: Vec<&String> = map.keys().collect();
+    assert_eq!(vec!["a", "c", "b"], keys);
+    assert_eq!("2", map["a"].to_string());
+}
+
+#[test]
+fn gen_nested() {
+    let mut rule = ObjectMap::new();
+    rule.insert(
+        String::from("outputs"),
+        AstNode::LIST(vec![
+            AstNode::STRVAL(String::from("out.txt")).boxed(),
+            AstNode::LIST(vec![]).boxed(),
+        ])
+        .boxed(),
+    );
+    let mut wf = ObjectMap::new();
+    wf.insert(
+        String::from("rules"),
+        AstNode::LIST(vec![AstNode::OBJECT(rule).boxed()]).boxed(),
+    );
+    assert_eq!(
+        "{\"rules\":[{\"outputs\":[\"out.txt\",[]]}]}",
+        json_gen::gen_json(&AstNode::OBJECT(wf)).unwrap()
+    );
+}
+
+#[test]
+fn gen_unevaluated() {
+    let node = AstNode::ADD {
+        left: AstNode::INTVAL(1).boxed(),
+        right: AstNode::INTVAL(2).boxed(),
+    };
+    assert!(json_gen::gen_json(&node).is_err());
+    assert!(json_gen::gen_json(&AstNode::DOUBLEVAL(f64::NAN)).is_err());
+}
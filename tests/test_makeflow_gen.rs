@@ -0,0 +1,137 @@
+extern crate jx2json;
+use jx2json::makeflow_gen::{self, MakeflowGenErr};
+use jx2json::symbol_tab::SymbolTable;
+use jx2json::workflow::{FileSpec, Job, Rule, Workflow};
+use jx2json::{eval, parser, scanner};
+
+#[test]
+fn gen_rules_and_categories() {
+    let workflow = workflow_src(
+        "{
+            \"environment\": { \"PATH\": \"/bin\", \"OPTS\": \"-n $N\" },
+            \"categories\": { \"big\": { \"resources\": { \"cores\": 4, \"memory\": 1024 } } },
+            \"rules\": [
+                { \"command\": \"sim > out.txt\", \"inputs\": [\"in.txt\", \"sim\"],
+                  \"outputs\": [{ \"dag_name\": \"out.txt\", \"task_name\": \"o\" }], \"category\": \"big\" },
+                { \"command\": \"cat out.txt > all.txt\", \"inputs\": [\"out.txt\"], \"outputs\": [\"all.txt\"],
+                  \"local_job\": true, \"environment\": { \"X\": \"it's\" } },
+                { \"command\": \"heavy\", \"outputs\": [\"h\"], \"category\": \"big\", \"resources\": { \"disk\": 100 } }
+            ]
+        }",
+    );
+    let expected = "\
+export PATH='/bin'
+export OPTS='-n $N'
+
+.MAKEFLOW CATEGORY big
+.MAKEFLOW CORES 4
+.MAKEFLOW MEMORY 1024
+
+out.txt->o: in.txt sim
+\tsim > out.txt
+
+.MAKEFLOW CATEGORY default
+all.txt: out.txt
+\tLOCAL export X='it'\\''s'; cat out.txt > all.txt
+
+.MAKEFLOW CATEGORY big-2
+.MAKEFLOW CORES 4
+.MAKEFLOW MEMORY 1024
+.MAKEFLOW DISK 100
+
+h:
+\theavy
+
+";
+    assert_eq!(expected, makeflow_gen::gen_makeflow(&workflow).unwrap());
+}
+
+#[test]
+fn gen_makeflow_errors() {
+    let workflow = workflow_src("{ \"rules\": [{ \"workflow\": \"sub.jx\" }] }");
+    assert!(matches!(
+        makeflow_gen::gen_makeflow(&workflow),
+        Err(MakeflowGenErr::Unsupported(_))
+    ));
+
+    let mut workflow =
+        workflow_src("{ \"rules\": [{ \"command\": \"a\\nb\", \"outputs\": [\"a\"] }] }");
+    assert!(matches!(
+        makeflow_gen::gen_makeflow(&workflow),
+        Err(MakeflowGenErr::MultiLine(_))
+    ));
+    let rule: &mut Rule = &mut workflow.rules[0];
+    rule.job = Job::Command(String::from("touch a"));
+    rule.outputs.push(FileSpec {
+        dag_name: String::from("out file"),
+        task_name: None,
+    });
+    match makeflow_gen::gen_makeflow(&workflow) {
+        Err(err @ MakeflowGenErr::InvalidName(_)) => assert_eq!(
+            "\"out file\" can not be written in Makeflow syntax",
+            err.to_string()
+        ),
+        other => panic!("{:?} should be InvalidName", other),
+    }
+
+    // a rule variable is exported on the command line
+    let workflow = workflow_src(
+        "{ \"rules\": [{ \"command\": \"a\", \"outputs\": [\"a\"], \"environment\": { \"X\": \"1\\n2\" } }] }",
+    );
+    assert!(matches!(
+        makeflow_gen::gen_makeflow(&workflow),
+        Err(MakeflowGenErr::MultiLine(_))
+    ));
+
+    // a rule without target
+    let workflow = workflow_src("{ \"rules\": [{ \"command\": \"a\", \"inputs\": [\"in\"] }] }");
+    assert!(matches!(
+        makeflow_gen::gen_makeflow(&workflow),
+        Err(MakeflowGenErr::Unsupported(_))
+    ));
+}
+
+#[test]
+fn gen_rule_category_names() {
+    // the copy of big for rule 0 can not be named big-0, which is taken
+    let workflow = workflow_src(
+        "{
+            \"categories\": { \"big\": { \"resources\": { \"cores\": 4 } },
+                            \"big-0\": { \"environment\": { \"X\": \"1\" } } },
+            \"rules\": [
+                { \"command\": \"a\", \"outputs\": [\"a\"], \"category\": \"big\", \"resources\": { \"gpus\": 1 } },
+                { \"command\": \"b\", \"outputs\": [\"b\"], \"category\": \"big-0\" }
+            ]
+        }",
+    );
+    let expected = "\
+.MAKEFLOW CATEGORY big
+.MAKEFLOW CORES 4
+
+.MAKEFLOW CATEGORY big-0
+export X='1'
+
+.MAKEFLOW CATEGORY big-0-1
+.MAKEFLOW CORES 4
+.MAKEFLOW GPUS 1
+
+a:
+\ta
+
+.MAKEFLOW CATEGORY big-0
+b:
+\tb
+
+";
+    assert_eq!(expected, makeflow_gen::gen_makeflow(&workflow).unwrap());
+}
+
+fn workflow_src(src: &str) -> Workflow {
+    let tokens = scanner::scan_token(String::from(src)).unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    let root = eval::eval(&result, &SymbolTable::new()).unwrap();
+    match Workflow::from_ast(&root) {
+        Ok(workflow) => workflow,
+        Err(errors) => panic!("{:?}", errors),
+    }
+}
@@ -1,15 +1,18 @@
 extern crate jx2json;
-use jx2json::parser;
-use jx2json::{ast::AstNode, jx_token::Token};
+use jx2json::ast::{Ast, AstNode};
+use jx2json::jx_token::{SpannedToken, Token, TokenKind};
+use jx2json::parser::{self, DuplicateKeys, ParseOptions, ParserErr};
+use jx2json::scanner;
+use jx2json::symbol_tab::Symbol;
 
 #[test]
 fn parse_empty_workflow1() {
     let input = vec![Token::LBRAC, Token::RBRAC];
-    let result = match parser::parse_tokens(input) {
+    let result = match parser::parse_tokens(spanned(input)) {
         Ok(result) => result,
         Err(err) => panic!("{}", err),
     };
-    if let AstNode::OBJECT(keyval_list) = result.root.as_ref() {
+    if let AstNode::OBJECT(keyval_list) = &result.root.node {
         assert!(keyval_list.is_empty());
     } else {
         panic!("{} should be OBJECT", result.root);
@@ -27,15 +30,15 @@ fn parse_empty_workflow2() {
         Token::RSQBRAC,
         Token::RBRAC,
     ];
-    let result = match parser::parse_tokens(input) {
+    let result = match parser::parse_tokens(spanned(input)) {
         Ok(result) => result,
         Err(err) => panic!("{}", err),
     };
-    if let AstNode::OBJECT(keyval_pairs) = result.root.as_ref() {
+    if let AstNode::OBJECT(keyval_pairs) = &result.root.node {
         assert_eq!(1, keyval_pairs.len());
         for (key, val) in keyval_pairs {
             assert_eq!("rules", key);
-            match val.as_ref() {
+            match &val.node {
                 // empty list
                 AstNode::LIST(list) => assert!(list.is_empty()),
                 _ => panic!("{} should be LIST", val),
@@ -73,7 +76,7 @@ fn parse_workflow1() {
         Token::RSQBRAC,
         Token::RBRAC,
     ];
-    let result = match parser::parse_tokens(input) {
+    let result = match parser::parse_tokens(spanned(input)) {
         Ok(result) => result,
         Err(err) => panic!("{}", err),
     };
@@ -85,21 +88,21 @@ fn parse_workflow1() {
             assert_eq!(3, keyval_pairs.len());
             keyval_pairs
         }
-        _ => panic!("{} should be OBJECT"),
+        _ => panic!("{} should be OBJECT", rule),
     };
     for (key, val) in keyval_pairs {
         match key.as_str() {
             // command should be a string
-            "command" => match val.as_ref() {
+            "command" => match &val.node {
                 AstNode::STRVAL(cmd) => assert_eq!("/bin/echo 'foo bar' > out.txt", cmd),
                 _ => panic!("{} should be STRVAL", val),
             },
             // outputs should be list contains 1 string
-            "outputs" => match val.as_ref() {
+            "outputs" => match &val.node {
                 AstNode::LIST(outputs) => {
                     assert_eq!(1, outputs.len());
                     let elem = outputs.first().unwrap();
-                    match elem.as_ref() {
+                    match &elem.node {
                         AstNode::STRVAL(val) => assert_eq!("out.txt", val),
                         _ => panic!("{} should be STRVAL", elem),
                     }
@@ -107,7 +110,7 @@ fn parse_workflow1() {
                 _ => panic!("{} should be STRVAL", val),
             },
             // inputs should be empty list
-            "inputs" => match val.as_ref() {
+            "inputs" => match &val.node {
                 AstNode::LIST(inputs) => assert!(inputs.is_empty()),
                 _ => panic!("{} should be STRVAL", val),
             },
@@ -118,7 +121,361 @@ fn parse_workflow1() {
     assert!(result.tab.count() == 0);
 }
 
-/*
+#[test]
+fn parse_arith_precedence() {
+    // 1 + 2 * 3
+    let expr = parse_expr(vec![
+        Token::INTCONST(1),
+        Token::ADD,
+        Token::INTCONST(2),
+        Token::MUL,
+        Token::INTCONST(3),
+    ]);
+    assert_eq!("1 + (2 * 3)", expr.to_string());
+
+    // 10 - 4 - 3
+    let expr = parse_expr(vec![
+        Token::INTCONST(10),
+        Token::MINUS,
+        Token::INTCONST(4),
+        Token::MINUS,
+        Token::INTCONST(3),
+    ]);
+    assert_eq!("(10 - 4) - 3", expr.to_string());
+}
+
+#[test]
+fn parse_paren_expr() {
+    // (1 + 2) * x % 3
+    let expr = parse_expr(vec![
+        Token::LPAREN,
+        Token::INTCONST(1),
+        Token::ADD,
+        Token::INTCONST(2),
+        Token::RPAREN,
+        Token::MUL,
+        Token::ID(String::from("x")),
+        Token::MOD,
+        Token::INTCONST(3),
+    ]);
+    assert_eq!("((1 + 2) * x) % 3", expr.to_string());
+}
+
+#[test]
+fn parse_unary_expr() {
+    // -5
+    let expr = parse_expr(vec![Token::MINUS, Token::INTCONST(5)]);
+    match &expr.node {
+        AstNode::INTVAL(val) => assert_eq!(-5, *val),
+        _ => panic!("{} should be INTVAL", expr),
+    }
+
+    // not a and -b > 1.5
+    let expr = parse_expr(vec![
+        Token::NOT,
+        Token::ID(String::from("a")),
+        Token::AND,
+        Token::MINUS,
+        Token::ID(String::from("b")),
+        Token::GT,
+        Token::DOUBLECONST(1.5),
+    ]);
+    assert_eq!("(not a) and ((-b) > 1.5)", expr.to_string());
+
+    // not 1 == 2, not binds looser than comparisons
+    let expr = parse_expr(vec![
+        Token::NOT,
+        Token::INTCONST(1),
+        Token::EQ,
+        Token::INTCONST(2),
+    ]);
+    assert_eq!("not (1 == 2)", expr.to_string());
+}
+
+#[test]
+fn parse_func_call() {
+    // format("%s.txt", basename(x)) + len([])
+    let expr = parse_expr(vec![
+        Token::ID(String::from("format")),
+        Token::LPAREN,
+        Token::STRCONST(String::from("%s.txt")),
+        Token::COMMA,
+        Token::ID(String::from("basename")),
+        Token::LPAREN,
+        Token::ID(String::from("x")),
+        Token::RPAREN,
+        Token::RPAREN,
+        Token::ADD,
+        Token::ID(String::from("len")),
+        Token::LPAREN,
+        Token::LSQBRAC,
+        Token::RSQBRAC,
+        Token::RPAREN,
+    ]);
+    assert_eq!("format(%s.txt, basename(x)) + len([  ])", expr.to_string());
+
+    // f()
+    let expr = parse_expr(vec![
+        Token::ID(String::from("f")),
+        Token::LPAREN,
+        Token::RPAREN,
+    ]);
+    match &expr.node {
+        AstNode::FUNC { name, params } => {
+            assert_eq!("f", name);
+            assert!(params.is_empty());
+        }
+        _ => panic!("{} should be FUNC", expr),
+    }
+}
+
+#[test]
+fn parse_null() {
+    // [null, x == null]
+    let expr = parse_expr(vec![
+        Token::LSQBRAC,
+        Token::NULL,
+        Token::COMMA,
+        Token::ID(String::from("x")),
+        Token::EQ,
+        Token::NULL,
+        Token::RSQBRAC,
+    ]);
+    assert_eq!("[ null, x == null ]", expr.to_string());
+}
+
+#[test]
+fn parse_computed_keys() {
+    // { name: 1, format("%d", n): 2, "a": 3 }
+    let expr = parse_expr(vec![
+        Token::LBRAC,
+        Token::ID(String::from("name")),
+        Token::COLON,
+        Token::INTCONST(1),
+        Token::COMMA,
+        Token::ID(String::from("format")),
+        Token::LPAREN,
+        Token::STRCONST(String::from("%d")),
+        Token::COMMA,
+        Token::ID(String::from("n")),
+        Token::RPAREN,
+        Token::COLON,
+        Token::INTCONST(2),
+        Token::COMMA,
+        Token::STRCONST(String::from("a")),
+        Token::COLON,
+        Token::INTCONST(3),
+        Token::RBRAC,
+    ]);
+    match &expr.node {
+        AstNode::OBJEXPR(pairs) => assert_eq!(3, pairs.len()),
+        _ => panic!("{} should be OBJEXPR", expr),
+    }
+    assert_eq!(
+        "{ name : 1, format(%d, n) : 2, \"a\" : 3 }",
+        expr.to_string()
+    );
+}
+
+#[test]
+fn parse_lookup_slice() {
+    // x[0][1:] + f(y)[:-1]
+    let expr = parse_expr(vec![
+        Token::ID(String::from("x")),
+        Token::LSQBRAC,
+        Token::INTCONST(0),
+        Token::RSQBRAC,
+        Token::LSQBRAC,
+        Token::INTCONST(1),
+        Token::COLON,
+        Token::RSQBRAC,
+        Token::ADD,
+        Token::ID(String::from("f")),
+        Token::LPAREN,
+        Token::ID(String::from("y")),
+        Token::RPAREN,
+        Token::LSQBRAC,
+        Token::COLON,
+        Token::INTCONST(-1),
+        Token::RSQBRAC,
+    ]);
+    match &expr.node {
+        AstNode::ADD { left, right } => {
+            assert!(matches!(left.node, AstNode::SLICE { .. }));
+            assert!(matches!(right.node, AstNode::SLICE { .. }));
+        }
+        _ => panic!("{} should be ADD", expr),
+    }
+    assert_eq!("x[0][1:] + f(y)[:-1]", expr.to_string());
+}
+
+#[test]
+fn parse_dot_access() {
+    // config.site["scratch"] is config["site"]["scratch"]
+    let expr = parse_expr(vec![
+        Token::ID(String::from("config")),
+        Token::DOT,
+        Token::ID(String::from("site")),
+        Token::LSQBRAC,
+        Token::STRCONST(String::from("scratch")),
+        Token::RSQBRAC,
+    ]);
+    match &expr.node {
+        AstNode::LOOKUP { expr, index } => {
+            assert!(matches!(&index.node, AstNode::STRVAL(key) if key == "scratch"));
+            assert!(
+                matches!(&expr.node, AstNode::LOOKUP { index, .. } if matches!(&index.node, AstNode::STRVAL(key) if key == "site"))
+            );
+        }
+        _ => panic!("{} should be LOOKUP", expr),
+    }
+    // attribute must be an identifier
+    let tokens = wrap_expr(vec![
+        Token::ID(String::from("config")),
+        Token::DOT,
+        Token::INTCONST(1),
+    ]);
+    assert!(matches!(
+        parser::parse_tokens(spanned(tokens)),
+        Err(ParserErr::UnexpectedToken {
+            context: "after `.`",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parse_conditional() {
+    // a if x > 1 else b if y else c, nests to the right
+    let expr = parse_expr(vec![
+        Token::ID(String::from("a")),
+        Token::IF,
+        Token::ID(String::from("x")),
+        Token::GT,
+        Token::INTCONST(1),
+        Token::ELSE,
+        Token::ID(String::from("b")),
+        Token::IF,
+        Token::ID(String::from("y")),
+        Token::ELSE,
+        Token::ID(String::from("c")),
+    ]);
+    match &expr.node {
+        AstNode::IFELSE { cond, orelse, .. } => {
+            assert!(matches!(cond.node, AstNode::GT { .. }));
+            assert!(matches!(orelse.node, AstNode::IFELSE { .. }));
+        }
+        _ => panic!("{} should be IFELSE", expr),
+    }
+    assert_eq!("a if (x > 1) else b if y else c", expr.to_string());
+    // else is required
+    let tokens = wrap_expr(vec![
+        Token::ID(String::from("a")),
+        Token::IF,
+        Token::ID(String::from("x")),
+    ]);
+    assert!(matches!(
+        parser::parse_tokens(spanned(tokens)),
+        Err(ParserErr::UnexpectedToken {
+            context: "in conditional",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn parse_logical_precedence() {
+    // a == 1 or b != 2 and c <= 3
+    let expr = parse_expr(vec![
+        Token::ID(String::from("a")),
+        Token::EQ,
+        Token::INTCONST(1),
+        Token::OR,
+        Token::ID(String::from("b")),
+        Token::NE,
+        Token::INTCONST(2),
+        Token::AND,
+        Token::ID(String::from("c")),
+        Token::LE,
+        Token::INTCONST(3),
+    ]);
+    assert_eq!("(a == 1) or ((b != 2) and (c <= 3))", expr.to_string());
+
+    // the printed expression is valid JX
+    let tokens = scanner::scan_token(expr.to_string()).unwrap();
+    let reparsed = parser::parse_expr(tokens).unwrap();
+    assert_eq!(expr.to_string(), reparsed.to_string());
+}
+
+#[test]
+fn parse_unbalanced_paren() {
+    let input = wrap_expr(vec![
+        Token::LPAREN,
+        Token::INTCONST(1),
+        Token::ADD,
+        Token::INTCONST(2),
+    ]);
+    assert!(parser::parse_tokens(spanned(input)).is_err());
+}
+
+#[test]
+fn parse_define() {
+    let input = vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("define")),
+        Token::COLON,
+        Token::LBRAC,
+        Token::STRCONST(String::from("n")),
+        Token::COLON,
+        Token::INTCONST(10),
+        Token::COMMA,
+        Token::STRCONST(String::from("m")),
+        Token::COLON,
+        Token::ID(String::from("n")),
+        Token::ADD,
+        Token::INTCONST(1),
+        Token::RBRAC,
+        Token::RBRAC,
+    ];
+    let result = match parser::parse_tokens(spanned(input)) {
+        Ok(result) => result,
+        Err(err) => panic!("{}", err),
+    };
+    assert_eq!(2, result.tab.count());
+    match result.tab.find(&String::from("m")) {
+        Some(Symbol::EXPR(expr)) => assert_eq!("n + 1", expr.to_string()),
+        _ => panic!("m should be defined"),
+    }
+
+    // define must be an object
+    let input = vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("define")),
+        Token::COLON,
+        Token::INTCONST(10),
+        Token::RBRAC,
+    ];
+    assert!(parser::parse_tokens(spanned(input)).is_err());
+}
+
+#[test]
+fn parse_single_expr() {
+    let expr = parser::parse_expr(spanned(vec![
+        Token::ID(String::from("N")),
+        Token::MUL,
+        Token::INTCONST(10),
+    ]));
+    match expr {
+        Ok(expr) => assert_eq!("N * 10", expr.to_string()),
+        Err(err) => panic!("{}", err),
+    }
+    // incomplete expression
+    assert!(parser::parse_expr(spanned(vec![Token::INTCONST(1), Token::ADD])).is_err());
+    // trailing tokens
+    assert!(parser::parse_expr(spanned(vec![Token::INTCONST(1), Token::INTCONST(2)])).is_err());
+    assert!(parser::parse_tokens(spanned(vec![Token::LBRAC, Token::RBRAC, Token::RBRAC])).is_err());
+}
+
 #[test]
 fn parse_workflow2() {
     let input = vec![
@@ -162,7 +519,7 @@ fn parse_workflow2() {
         Token::RSQBRAC,
         Token::RBRAC,
     ];
-    let result = match parser::parse_tokens(input) {
+    let result = match parser::parse_tokens(spanned(input)) {
         Ok(result) => result,
         Err(err) => panic!("{}", err),
     };
@@ -174,29 +531,29 @@ fn parse_workflow2() {
             assert_eq!(3, keyval_pairs.len());
             keyval_pairs
         }
-        _ => panic!("{} should be OBJECT"),
+        _ => panic!("{} should be OBJECT", rule),
     };
     for (key, val) in keyval_pairs {
         match key.as_str() {
             // command should be a string
-            "command" => match val.as_ref() {
+            "command" => match &val.node {
                 AstNode::STRVAL(cmd) => assert_eq!("/bin/echo 'foo bar' > out.txt", cmd),
                 _ => panic!("{} should be STRVAL", val),
             },
             // outputs should be list contains 1 element
             "outputs" => {
                 assert!(val.is_list());
-                if let AstNode::LIST(outputs) = val.as_ref() {
+                if let AstNode::LIST(outputs) = &val.node {
                     assert_eq!(1, outputs.len());
                     let elem = outputs.first().unwrap();
-                    match elem.as_ref() {
+                    match &elem.node {
                         AstNode::ADD { left, right } => {
-                            assert!(left.as_ref().is_str());
-                            if let AstNode::STRVAL(val) = left.as_ref() {
+                            assert!(left.is_str());
+                            if let AstNode::STRVAL(val) = &left.node {
                                 assert_eq!("out", val);
                             }
-                            assert!(right.as_ref().is_str());
-                            if let AstNode::STRVAL(val) = right.as_ref() {
+                            assert!(right.is_str());
+                            if let AstNode::STRVAL(val) = &right.node {
                                 assert_eq!(".txt", val);
                             }
                         }
@@ -205,23 +562,24 @@ fn parse_workflow2() {
                 }
             }
             // inputs should be list contains 1 element
-            "inputs" => match val.as_ref() {
+            "inputs" => match &val.node {
                 AstNode::LIST(inputs) => {
                     assert_eq!(1, inputs.len());
                     let elem = inputs.first().unwrap();
                     assert!(elem.is_list_compre());
-                    if let AstNode::COMPRE {
-                        expr,
-                        var,
-                        iter_expr,
-                    } = elem.as_ref()
-                    {
-                        match expr.as_ref() {
-                            AstNode::ADD { left, right } => (/* FIXME */),
+                    if let AstNode::COMPRE { expr, clauses } = &elem.node {
+                        // ("in" + x1) + ".txt"
+                        match &expr.node {
+                            AstNode::ADD { left, right } => {
+                                assert_eq!("in + x1", left.to_string());
+                                assert!(right.is_str());
+                            }
                             _ => panic!("{} should be ADD", expr),
                         };
-                        assert_eq!("x1", var);
-                        assert!(iter_expr.as_ref().is_list());
+                        assert_eq!(1, clauses.len());
+                        assert_eq!("x1", clauses[0].var);
+                        assert!(clauses[0].iter_expr.is_list());
+                        assert!(clauses[0].conds.is_empty());
                     }
                 }
                 _ => panic!("{} should be LIST", val),
@@ -229,9 +587,303 @@ fn parse_workflow2() {
             _ => (),
         };
     }
-    assert!(result.tab.count() == 1);
+    // loop variable is not a symbol
+    assert!(result.tab.count() == 0);
+}
+
+#[test]
+fn parse_multi_clause_compre() {
+    // [ x * y for x in xs if x > 1 for y in [x, 2] if y != x ]
+    let expr = parse_expr(vec![
+        Token::LSQBRAC,
+        Token::ID(String::from("x")),
+        Token::MUL,
+        Token::ID(String::from("y")),
+        Token::FOR,
+        Token::ID(String::from("x")),
+        Token::IN,
+        Token::ID(String::from("xs")),
+        Token::IF,
+        Token::ID(String::from("x")),
+        Token::GT,
+        Token::INTCONST(1),
+        Token::FOR,
+        Token::ID(String::from("y")),
+        Token::IN,
+        Token::LSQBRAC,
+        Token::ID(String::from("x")),
+        Token::COMMA,
+        Token::INTCONST(2),
+        Token::RSQBRAC,
+        Token::IF,
+        Token::ID(String::from("y")),
+        Token::NE,
+        Token::ID(String::from("x")),
+        Token::RSQBRAC,
+    ]);
+    let list = match &expr.node {
+        AstNode::LIST(list) => list,
+        _ => panic!("{} should be LIST", expr),
+    };
+    assert_eq!(1, list.len());
+    match &list[0].node {
+        AstNode::COMPRE { expr, clauses } => {
+            assert_eq!("x * y", expr.to_string());
+            assert_eq!(2, clauses.len());
+            assert_eq!("for x in xs if x > 1", clauses[0].to_string());
+            assert_eq!("for y in [ x, 2 ] if y != x", clauses[1].to_string());
+        }
+        _ => panic!("{} should be COMPRE", list[0]),
+    }
+
+    // missing loop variable
+    let input = wrap_expr(vec![
+        Token::LSQBRAC,
+        Token::INTCONST(1),
+        Token::FOR,
+        Token::IN,
+        Token::ID(String::from("xs")),
+        Token::RSQBRAC,
+    ]);
+    assert!(parser::parse_tokens(spanned(input)).is_err());
+}
+
+#[test]
+fn parse_error_location() {
+    let src = "{\n  \"rules\": [\n    { \"command\" \"echo\" }\n  ]\n}";
+    let tokens = scanner::scan_source(String::from(src), "rules.jx").unwrap();
+    let err = match parser::parse_tokens(tokens) {
+        Err(err) => err,
+        Ok(_) => panic!("should be error"),
+    };
+    assert_eq!(
+        "expected `:` after key, found string \"echo\" at rules.jx:3:17",
+        err.to_string()
+    );
+    assert_eq!(
+        (3, 17, 31, 37),
+        (
+            err.span().line,
+            err.span().col,
+            err.span().start,
+            err.span().end
+        )
+    );
+    assert!(err
+        .render()
+        .ends_with("3 |     { \"command\" \"echo\" }\n  |                 ^^^^^^\n"));
+
+    let tokens = scanner::scan_source(String::from("{ \"a\": [1, 2 + }"), "wf.jx").unwrap();
+    match parser::parse_tokens(tokens) {
+        Err(err) => assert_eq!(
+            "expected expression, found `}` at wf.jx:1:16",
+            err.to_string()
+        ),
+        Ok(_) => panic!("should be error"),
+    }
+
+    // span of node covers the whole expression
+    let tokens = scanner::scan_source(String::from("{ \"a\": f(x, 1) * 2 }"), "wf.jx").unwrap();
+    let root = parser::parse_tokens(tokens).unwrap().root;
+    match &root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            let span = &keyval_pairs["a"].span;
+            assert_eq!((7, 18), (span.start, span.end));
+        }
+        _ => panic!("{} should be OBJECT", root),
+    }
+}
+
+#[test]
+fn parse_structured_errors() {
+    // { "a" 1 }
+    match parser::parse_tokens(spanned(vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("a")),
+        Token::INTCONST(1),
+        Token::RBRAC,
+    ])) {
+        Err(ParserErr::UnexpectedToken {
+            expected, found, ..
+        }) => {
+            assert_eq!(vec![TokenKind::COLON], expected);
+            assert!(found.node == Token::INTCONST(1));
+        }
+        _ => panic!("should be UnexpectedToken"),
+    }
+
+    // { "a": [1,
+    match parser::parse_tokens(spanned(vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("a")),
+        Token::COLON,
+        Token::LSQBRAC,
+        Token::INTCONST(1),
+        Token::COMMA,
+    ])) {
+        Err(err @ ParserErr::UnexpectedEof { .. }) => {
+            assert!(err
+                .to_string()
+                .starts_with("unexpected end of input, expected expression"))
+        }
+        _ => panic!("should be UnexpectedEof"),
+    }
+
+    let tokens = scanner::scan_source(String::from("{ \"a\": 1,\n  \"a\": 2 }"), "wf.jx").unwrap();
+    match parser::parse_tokens(tokens) {
+        Err(err @ ParserErr::DuplicateKey { .. }) => assert_eq!(
+            "duplicate key \"a\" at wf.jx:2:3, first defined at wf.jx:1:3",
+            err.to_string()
+        ),
+        _ => panic!("should be DuplicateKey"),
+    }
+
+    // [ x for 1 in xs ]
+    let input = wrap_expr(vec![
+        Token::LSQBRAC,
+        Token::ID(String::from("x")),
+        Token::FOR,
+        Token::INTCONST(1),
+        Token::IN,
+        Token::ID(String::from("xs")),
+        Token::RSQBRAC,
+    ]);
+    assert!(matches!(
+        parser::parse_tokens(spanned(input)),
+        Err(ParserErr::InvalidComprehension { .. })
+    ));
+
+    // { "define": 1 }
+    match parser::parse_tokens(spanned(vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("define")),
+        Token::COLON,
+        Token::INTCONST(1),
+        Token::RBRAC,
+    ])) {
+        Err(ParserErr::InvalidDefine { found, .. }) => assert_eq!("int", found),
+        _ => panic!("should be InvalidDefine"),
+    }
+}
+
+#[test]
+fn parse_with_comments() {
+    let input = "# rules\n{ \"a\": 1, # one\n  \"b\": [ # list\n 2 ] }";
+    let options = scanner::ScanOptions {
+        keep_comments: true,
+        ..scanner::ScanOptions::default()
+    };
+    let tokens = scanner::scan_with(String::from(input), "wf.jx", options).unwrap();
+    assert_eq!(14, tokens.len());
+    // comments are skipped by the parser
+    let result = parser::parse_tokens(tokens).unwrap();
+    match &result.root.node {
+        AstNode::OBJECT(keyval_pairs) => assert_eq!(2, keyval_pairs.len()),
+        other => panic!("{} should be OBJECT", other),
+    }
+}
+
+#[test]
+fn parse_duplicate_key_policy() {
+    let input = "{ \"r\": { \"outputs\": [\"a\"], \"outputs\": [\"b\"] } }";
+    let parse = |duplicate_keys| {
+        let tokens = scanner::scan_source(String::from(input), "wf.jx").unwrap();
+        parser::parse_tokens_recover(tokens, ParseOptions { duplicate_keys })
+    };
+
+    let (_, errors) = parse(DuplicateKeys::ERROR);
+    match errors.as_slice() {
+        [err @ ParserErr::DuplicateKey { .. }] => assert_eq!(
+            "duplicate key \"outputs\" at wf.jx:1:28, first defined at wf.jx:1:10",
+            err.to_string()
+        ),
+        _ => panic!("should be 1 DuplicateKey"),
+    }
+
+    for (policy, expected) in [
+        (DuplicateKeys::KEEPLAST, "[ b ]"),
+        (DuplicateKeys::KEEPFIRST, "[ a ]"),
+    ] {
+        let (result, errors) = parse(policy);
+        assert!(errors.is_empty());
+        let result = result.unwrap();
+        assert_eq!(1, result.warnings.len());
+        let rule = match &result.root.node {
+            AstNode::OBJECT(keyval_pairs) => &keyval_pairs["r"],
+            other => panic!("{} should be OBJECT", other),
+        };
+        match &rule.node {
+            AstNode::OBJECT(keyval_pairs) => {
+                assert_eq!(1, keyval_pairs.len());
+                assert_eq!(expected, keyval_pairs["outputs"].to_string());
+            }
+            other => panic!("{} should be OBJECT", other),
+        }
+    }
+}
+
+#[test]
+fn parse_error_recovery() {
+    let input = "{\n  \"a\": 1 +,\n  \"b\": [1 2, 3],\n  \"c\": [\"x\", ],\n  \"d\": { \"e\": [1 },\n  \"f\": 4\n}";
+    let tokens = scanner::scan_source(String::from(input), "wf.jx").unwrap();
+    let (result, errors) = parser::parse_tokens_recover(tokens, ParseOptions::default());
+    let locations: Vec<String> = errors.iter().map(|err| err.span().to_string()).collect();
+    assert_eq!(
+        vec!["wf.jx:2:11", "wf.jx:3:11", "wf.jx:4:14", "wf.jx:5:18"],
+        locations
+    );
+
+    // erroneous values are left out of the partial AST
+    let result = result.unwrap();
+    match &result.root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert!(!keyval_pairs.contains_key("a"));
+            assert_eq!("[ 1, 3 ]", keyval_pairs["b"].to_string());
+            assert_eq!("[ x ]", keyval_pairs["c"].to_string());
+            assert_eq!("{ \"e\" : [ 1 ] }", keyval_pairs["d"].to_string());
+            assert_eq!("4", keyval_pairs["f"].to_string());
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+
+    // recovery stops at the `)` of a call, the rest of the object is parsed as usual
+    let call = "{\"a\": f(1 2), \"b\": 3}";
+    let tokens = scanner::scan_source(String::from(call), "wf.jx").unwrap();
+    let (result, errors) = parser::parse_tokens_recover(tokens, ParseOptions::default());
+    let locations: Vec<String> = errors.iter().map(|err| err.span().to_string()).collect();
+    assert_eq!(vec!["wf.jx:1:11"], locations);
+    match &result.unwrap().root.node {
+        AstNode::OBJECT(keyval_pairs) => {
+            assert_eq!("f(1)", keyval_pairs["a"].to_string());
+            assert_eq!("3", keyval_pairs["b"].to_string());
+        }
+        other => panic!("{} should be OBJECT", other),
+    }
+
+    // the first error is returned without recovery
+    match parser::parse_tokens(scanner::scan_source(String::from(input), "wf.jx").unwrap()) {
+        Err(err) => assert_eq!("wf.jx:2:11", err.span().to_string()),
+        Ok(_) => panic!("should be an error"),
+    }
+}
+
+#[test]
+fn parse_nesting_limit() {
+    let nested = |depth: usize| format!("{{\"a\": {}{}}}", "[".repeat(depth), "]".repeat(depth));
+    let tokens = scanner::scan_source(nested(parser::MAX_DEPTH), "wf.jx").unwrap();
+    assert!(parser::parse_tokens(tokens).is_ok());
+
+    // deep input is an error, not a stack overflow, even with recovery
+    let tokens = scanner::scan_source(nested(50_000), "wf.jx").unwrap();
+    let (_, errors) = parser::parse_tokens_recover(tokens, ParseOptions::default());
+    match &errors[..] {
+        [err @ ParserErr::TooDeep { .. }] => assert_eq!(
+            "nesting deeper than 128 levels at wf.jx:1:135",
+            err.to_string()
+        ),
+        other => panic!("{:?} should be one TooDeep", other),
+    }
 }
-*/
 
 /// assert the wf only has "rules" key, and 1 rules
 /// return the rule AstNode
@@ -245,7 +897,7 @@ fn check_if_1_rule_wf(root: &AstNode) -> &AstNode {
     assert_eq!(1, keyval_pairs.len());
     assert_eq!("rules", keyval_pairs.keys().next().unwrap());
     let rules = keyval_pairs.values().next().unwrap();
-    let rule = match rules.as_ref() {
+    let rule = match &rules.node {
         // empty list
         AstNode::LIST(list) => {
             assert_eq!(1, list.len());
@@ -255,3 +907,32 @@ fn check_if_1_rule_wf(root: &AstNode) -> &AstNode {
     };
     rule
 }
+
+/// wrap expression tokens as the value of the only key in workflow
+fn wrap_expr(expr: Vec<Token>) -> Vec<Token> {
+    let mut input = vec![
+        Token::LBRAC,
+        Token::STRCONST(String::from("expr")),
+        Token::COLON,
+    ];
+    input.extend(expr);
+    input.push(Token::RBRAC);
+    input
+}
+
+/// parse expression tokens, return the AST of the expression
+fn parse_expr(expr: Vec<Token>) -> Ast {
+    let result = match parser::parse_tokens(spanned(wrap_expr(expr))) {
+        Ok(result) => result,
+        Err(err) => panic!("{}", err),
+    };
+    match result.root.node {
+        AstNode::OBJECT(mut keyval_pairs) => keyval_pairs.remove("expr").unwrap(),
+        _ => panic!("root should be OBJECT"),
+    }
+}
+
+/// tokens without location
+fn spanned(tokens: Vec<Token>) -> Vec<SpannedToken> {
+    tokens.into_iter().map(SpannedToken::from).collect()
+}
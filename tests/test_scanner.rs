@@ -1,6 +1,8 @@
+// these tests compare with assert_eq!(true, ..) and take &Vec, as they were written
+#![allow(clippy::bool_assert_comparison, clippy::ptr_arg)]
 extern crate jx2json;
 
-use jx2json::jx_token::Token;
+use jx2json::jx_token::{SpannedToken, Token};
 use jx2json::scanner;
 
 #[test]
@@ -143,15 +145,107 @@ fn scan_tokens3() {
     }
 }
 
-fn compare_tokens(tokens: &Vec<Token>, expected: &Vec<Token>) -> bool {
+#[test]
+fn scan_tokens_end_of_input() {
+    let result = scanner::scan_token(String::from("N * 10"));
+    let expected = vec![
+        Token::ID(String::from("N")),
+        Token::MUL,
+        Token::INTCONST(10),
+    ];
+    match result {
+        Ok(tokens) => {
+            assert_eq!(expected.len(), tokens.len());
+            assert!(compare_tokens(&tokens, &expected));
+        }
+        Err(err) => panic!("Should not have error: {}", err),
+    }
+
+    // unterminated string
+    assert!(scanner::scan_token(String::from("\"abc")).is_err());
+}
+
+#[test]
+fn scan_token_spans() {
+    let tokens =
+        scanner::scan_source(String::from("{\n  \"a\" : 10,\n\t\"é\": x}"), "wf.jx").unwrap();
+    let spans: Vec<(String, usize, usize)> = tokens
+        .iter()
+        .map(|token| (token.span.to_string(), token.span.start, token.span.end))
+        .collect();
+    assert_eq!(
+        vec![
+            (String::from("wf.jx:1:1"), 0, 1),
+            (String::from("wf.jx:2:3"), 4, 7),
+            (String::from("wf.jx:2:7"), 8, 9),
+            (String::from("wf.jx:2:9"), 10, 12),
+            (String::from("wf.jx:2:11"), 12, 13),
+            (String::from("wf.jx:3:2"), 15, 19),
+            (String::from("wf.jx:3:5"), 19, 20),
+            (String::from("wf.jx:3:7"), 21, 22),
+            (String::from("wf.jx:3:8"), 22, 23),
+        ],
+        spans
+    );
+
+    match scanner::scan_source(String::from("{\n  \"a\": 1 $ }"), "wf.jx") {
+        Err(err) => {
+            assert_eq!("unexpected character '$' at wf.jx:2:10", err.to_string());
+            assert_eq!(
+                "unexpected character '$' at wf.jx:2:10\n \
+                 --> wf.jx:2:10\n  |\n2 |   \"a\": 1 $ }\n  |          ^\n",
+                err.render()
+            );
+        }
+        Ok(_) => panic!("should be error"),
+    }
+}
+
+#[test]
+fn scan_large_input() {
+    // a long string and many small tokens
+    let long = "x".repeat(2_000_000);
+    let mut input = format!("{{ \"long\": \"{}\", \"list\": [", long);
+    for i in 0..100_000 {
+        input.push_str(&format!("{}, ", i));
+    }
+    input.push_str("0] }");
+    let tokens = scanner::scan_token(input).unwrap();
+    assert_eq!(11 + 2 * 100_000, tokens.len());
+    assert!(tokens[3].node == Token::STRCONST(long));
+
+    // tokens are produced lazily, the error comes after the valid ones
+    let mut lexer = scanner::Scanner::new(String::from("[1, 2 $"), "<input>");
+    assert!(lexer.by_ref().take(4).all(|token| token.is_ok()));
+    assert!(lexer.next().unwrap().is_err());
+    assert!(lexer.next().is_none());
+}
+
+#[test]
+fn scan_string_escape_errors() {
+    match scanner::scan_source(String::from("{\n  \"a\": \"x\\qy\" }"), "wf.jx") {
+        Err(err) => assert_eq!(
+            "invalid escape sequence '\\q' at wf.jx:2:10\n \
+             --> wf.jx:2:10\n  |\n2 |   \"a\": \"x\\qy\" }\n  |          ^^\n",
+            err.render()
+        ),
+        Ok(_) => panic!("should be error"),
+    }
+    match scanner::scan_source(String::from("[\"a\\\"]"), "wf.jx") {
+        Err(err) => assert_eq!("unterminated string at wf.jx:1:2", err.to_string()),
+        Ok(_) => panic!("should be error"),
+    }
+}
+
+fn compare_tokens(tokens: &Vec<SpannedToken>, expected: &Vec<Token>) -> bool {
     let matching = tokens
         .iter()
         .zip(expected)
         .filter(|&(token, expect)| {
-            if token != expect {
+            if token.node != *expect {
                 eprintln!("token mismatched, got {}, expect {}", token, expect);
             };
-            token == expect
+            token.node == *expect
         })
         .count();
     tokens.len() == matching
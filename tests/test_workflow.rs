@@ -0,0 +1,149 @@
+extern crate jx2json;
+use jx2json::ast::{Ast, AstNode};
+use jx2json::eval;
+use jx2json::json_gen;
+use jx2json::parser;
+use jx2json::scanner;
+use jx2json::symbol_tab::SymbolTable;
+use jx2json::workflow::{self, FileSpec, Job, Workflow, WorkflowErr};
+
+#[test]
+fn validate_workflow() {
+    let root = eval_src(
+        "{
+            \"define\": { \"n\": 2 },
+            \"categories\": { \"big\": { \"resources\": { \"cores\": 4, \"memory\": 1024 } } },
+            \"environment\": { \"PATH\": \"/bin\" },
+            \"rules\": [
+                { \"command\": format(\"sim %d\", n), \"inputs\": [\"in.txt\"],
+                  \"outputs\": [{ \"dag_name\": \"out.txt\", \"task_name\": \"o\" }],
+                  \"category\": \"big\", \"local_job\": false },
+                { \"workflow\": \"sub.jx\", \"args\": { \"n\": n } }
+            ]
+        }",
+    );
+    let errors = workflow::validate(&root);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn validate_rules() {
+    let root = eval_src(
+        "{ \"rules\": [
+            { \"command\": \"a\", \"workflow\": \"b\" },
+            { \"command\": 1, \"inputs\": \"x\", \"outputs\": [2, {\"task_name\": \"t\"}] },
+            { \"comand\": \"a\" }
+        ] }",
+    );
+    let errors = workflow::validate(&root);
+    assert_eq!(
+        vec![
+            "$.rules[0]",
+            "$.rules[1].command",
+            "$.rules[1].inputs",
+            "$.rules[1].outputs[0]",
+            "$.rules[1].outputs[1]",
+            "$.rules[2]",
+            "$.rules[2].comand",
+        ],
+        paths(&errors)
+    );
+}
+
+#[test]
+fn validate_errors() {
+    let root = eval_src("{ \"environment\": { \"PATH\": 1 }, \"extra\": true }");
+    let errors = workflow::validate(&root);
+    assert_eq!(3, errors.len());
+    match &errors[0] {
+        err @ WorkflowErr::MissingKey { key: "rules", .. } => {
+            assert_eq!("$: missing \"rules\" at <test>:1:1", err.to_string())
+        }
+        other => panic!("{} should be MissingKey", other),
+    }
+    match &errors[1] {
+        err @ WorkflowErr::WrongType { .. } => {
+            assert_eq!(
+                "$.environment.PATH: expected string, found int at <test>:1:28",
+                err.to_string()
+            )
+        }
+        other => panic!("{} should be WrongType", other),
+    }
+    assert!(matches!(&errors[2], WorkflowErr::UnknownKey { path, .. } if path == "$.extra"));
+
+    // negative resource and root that is not an object
+    let root = eval_src(
+        "{ \"rules\": [], \"categories\": { \"c\": { \"resources\": { \"cores\": -1 } } } }",
+    );
+    let errors = workflow::validate(&root);
+    assert!(matches!(&errors[..], [WorkflowErr::InvalidValue { .. }]));
+    let errors = workflow::validate(&AstNode::LIST(vec![]).boxed());
+    assert!(matches!(
+        &errors[..],
+        [WorkflowErr::WrongType {
+            expected: "object",
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn workflow_model() {
+    let root = eval_src(
+        "{
+            \"categories\": { \"big\": { \"resources\": { \"cores\": 4, \"memory\": 1024 } } },
+            \"rules\": [
+                { \"command\": \"sim > out.txt\", \"inputs\": [\"in.txt\"],
+                  \"outputs\": [{ \"dag_name\": \"out.txt\", \"task_name\": \"o\" }],
+                  \"category\": \"big\", \"environment\": { \"MODE\": \"fast\" } },
+                { \"workflow\": \"sub.jx\", \"local_job\": true, \"resources\": { \"gpus\": 1 } }
+            ]
+        }",
+    );
+    let workflow = match Workflow::from_ast(&root) {
+        Ok(workflow) => workflow,
+        Err(errors) => panic!("{:?}", errors),
+    };
+    assert_eq!(2, workflow.rules.len());
+    let rule = &workflow.rules[0];
+    assert!(matches!(&rule.job, Job::Command(command) if command == "sim > out.txt"));
+    assert_eq!(
+        vec![FileSpec {
+            dag_name: String::from("out.txt"),
+            task_name: Some(String::from("o")),
+        }],
+        rule.outputs
+    );
+    assert_eq!(Some("fast"), rule.environment.get("MODE"));
+    assert_eq!(Some(1), workflow.rules[1].resources.gpus);
+    assert!(workflow.rules[1].local_job);
+    assert_eq!("big", workflow.categories[0].name);
+    assert_eq!(Some(1024), workflow.categories[0].resources.memory);
+
+    // back to the document, defaults are left out
+    assert_eq!(
+        "{\"categories\":{\"big\":{\"resources\":{\"cores\":4,\"memory\":1024}}},\"rules\":[\
+        {\"command\":\"sim > out.txt\",\"inputs\":[\"in.txt\"],\"outputs\":[{\"dag_name\":\"out.txt\",\"task_name\":\"o\"}],\
+        \"category\":\"big\",\"environment\":{\"MODE\":\"fast\"}},\
+        {\"workflow\":\"sub.jx\",\"local_job\":true,\"resources\":{\"gpus\":1}}]}",
+        json_gen::gen_json(&workflow.to_ast()).unwrap()
+    );
+
+    // mismatches are the validation errors
+    let root = eval_src("{ \"rules\": [{ \"command\": \"a\", \"resources\": { \"cpus\": 1 } }] }");
+    match Workflow::from_ast(&root) {
+        Err(errors) => assert_eq!(vec!["$.rules[0].resources.cpus"], paths(&errors)),
+        Ok(_) => panic!("should be an error"),
+    }
+}
+
+fn paths(errors: &[WorkflowErr]) -> Vec<&str> {
+    errors.iter().map(|err| err.path()).collect()
+}
+
+fn eval_src(src: &str) -> Ast {
+    let tokens = scanner::scan_source(String::from(src), "<test>").unwrap();
+    let result = parser::parse_tokens(tokens).unwrap();
+    eval::eval(&result, &SymbolTable::new()).unwrap()
+}